@@ -0,0 +1,38 @@
+//! src/cancel.rs
+
+use colored::Colorize;
+use console::Term;
+use std::time::Duration;
+
+/// 被 Ctrl-C 取消时的退出码 (128 + SIGINT)
+pub const EXIT_CODE_CANCELLED: i32 = 130;
+
+/// 等待 Ctrl-C。如果无法注册信号处理器，则永远不会返回。
+pub async fn wait_for_interrupt() {
+    if tokio::signal::ctrl_c().await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// 清理进度条残留的行并恢复光标。
+pub fn restore_terminal() {
+    let term = Term::stderr();
+    term.clear_line().ok();
+    term.show_cursor().ok();
+}
+
+pub fn exit_cancelled() -> ! {
+    restore_terminal();
+    eprintln!("{}", "⛔ 操作已取消。".yellow());
+    std::process::exit(EXIT_CODE_CANCELLED)
+}
+
+/// 兜底的 Ctrl-C 监听：主任务卡在同步的交互提示里时无法响应取消，
+/// 这里稍等片刻后直接恢复终端并退出。
+pub fn spawn_interrupt_watchdog() {
+    tokio::spawn(async {
+        wait_for_interrupt().await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        exit_cancelled();
+    });
+}
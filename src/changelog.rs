@@ -0,0 +1,397 @@
+//! src/changelog.rs
+//!
+//! 把 Conventional Commits 格式的提交整理成 Keep a Changelog 风格的版本段落，
+//! 并把新段落插入到已有的 CHANGELOG.md 中。
+
+use crate::git_backend::CommitInfo;
+use anyhow::{Result, anyhow};
+use chrono::NaiveDate;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fmt;
+
+lazy_static! {
+    static ref HEADER: Regex = Regex::new(
+        r"^(?P<type>[A-Za-z]+)(?:\((?P<scope>[^()]*)\))?(?P<bang>!)?:\s*(?P<description>\S.*)$"
+    )
+    .unwrap();
+    static ref BREAKING_FOOTER: Regex =
+        Regex::new(r"^BREAKING[ -]CHANGE:\s*(?P<note>.*)$").unwrap();
+}
+
+/// Keep a Changelog 的章节，按输出顺序排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Section {
+    Added,
+    Changed,
+    Deprecated,
+    Removed,
+    Fixed,
+    Security,
+}
+
+impl Section {
+    /// 提交类型对应的章节；docs、chore、test 等不面向使用者的类型不进入变更日志
+    fn from_type(kind: &str) -> Option<Self> {
+        match kind.to_ascii_lowercase().as_str() {
+            "feat" => Some(Self::Added),
+            "fix" => Some(Self::Fixed),
+            "perf" | "refactor" | "revert" => Some(Self::Changed),
+            "deprecate" => Some(Self::Deprecated),
+            "remove" => Some(Self::Removed),
+            "security" | "sec" => Some(Self::Security),
+            _ => None,
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Self::Added => "Added",
+            Self::Changed => "Changed",
+            Self::Deprecated => "Deprecated",
+            Self::Removed => "Removed",
+            Self::Fixed => "Fixed",
+            Self::Security => "Security",
+        }
+    }
+}
+
+/// Conventional Commits 格式的标题，例如 `feat(cli)!: add --json`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub kind: String,
+    pub scope: Option<String>,
+    /// 标题中带有 `!`
+    pub breaking: bool,
+    pub description: String,
+}
+
+impl Header {
+    pub fn parse(subject: &str) -> Option<Self> {
+        let captures = HEADER.captures(subject.trim())?;
+        Some(Header {
+            kind: captures["type"].to_string(),
+            scope: captures
+                .name("scope")
+                .map(|scope| scope.as_str().trim().to_string())
+                .filter(|scope| !scope.is_empty()),
+            breaking: captures.name("bang").is_some(),
+            description: captures["description"].trim().to_string(),
+        })
+    }
+}
+
+/// 变更日志中的一条记录
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub scope: Option<String>,
+    pub description: String,
+    pub short_sha: String,
+}
+
+impl Entry {
+    fn render(&self) -> String {
+        match &self.scope {
+            Some(scope) => format!("- **{scope}:** {} ({})", self.description, self.short_sha),
+            None => format!("- {} ({})", self.description, self.short_sha),
+        }
+    }
+}
+
+/// 一个版本的变更日志
+#[derive(Debug, Default)]
+pub struct Release {
+    /// 版本号，为空时表示 Unreleased
+    pub version: Option<String>,
+    pub date: Option<NaiveDate>,
+    pub breaking: Vec<Entry>,
+    pub sections: BTreeMap<Section, Vec<Entry>>,
+    /// 没有进入变更日志的提交数 (非 Conventional Commits 格式或不面向使用者的类型)
+    pub skipped: usize,
+}
+
+impl Release {
+    /// `commits` 的顺序与 `git log` 相同 (从新到旧)，输出时按从旧到新排列
+    pub fn from_commits(
+        version: Option<String>,
+        date: Option<NaiveDate>,
+        commits: &[CommitInfo],
+    ) -> Self {
+        let mut release = Release {
+            version,
+            date,
+            ..Default::default()
+        };
+
+        for commit in commits.iter().rev() {
+            let Some(Header {
+                kind,
+                scope,
+                breaking,
+                description,
+            }) = Header::parse(commit.subject())
+            else {
+                release.skipped += 1;
+                continue;
+            };
+            let breaking_note = commit
+                .message
+                .lines()
+                .skip(1)
+                .find_map(|line| BREAKING_FOOTER.captures(line.trim()))
+                .map(|footer| footer["note"].trim().to_string())
+                .filter(|note| !note.is_empty());
+            let is_breaking = breaking || breaking_note.is_some();
+
+            // 破坏性变更只在最前面列出一次，说明优先使用 footer 中的描述
+            if is_breaking {
+                release.breaking.push(Entry {
+                    scope,
+                    description: breaking_note.unwrap_or(description),
+                    short_sha: commit.short_sha().to_string(),
+                });
+                continue;
+            }
+            match Section::from_type(&kind) {
+                Some(section) => release.sections.entry(section).or_default().push(Entry {
+                    scope,
+                    description,
+                    short_sha: commit.short_sha().to_string(),
+                }),
+                None => release.skipped += 1,
+            }
+        }
+        release
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breaking.is_empty() && self.sections.is_empty()
+    }
+
+    /// 按提交类型建议的版本升级：破坏性变更升主版本号，新功能升次版本号，其余升修订号
+    pub fn suggested_bump(&self) -> Bump {
+        if !self.breaking.is_empty() {
+            Bump::Major
+        } else if self.sections.contains_key(&Section::Added) {
+            Bump::Minor
+        } else {
+            Bump::Patch
+        }
+    }
+
+    /// 版本标题中方括号里的部分，例如 `1.2.0` 或 `Unreleased`
+    pub fn label(&self) -> &str {
+        self.version.as_deref().unwrap_or("Unreleased")
+    }
+
+    pub fn render(&self) -> String {
+        let mut lines = vec![match self.date {
+            Some(date) => format!("## [{}] - {}", self.label(), date.format("%Y-%m-%d")),
+            None => format!("## [{}]", self.label()),
+        }];
+
+        let mut push_section = |title: &str, entries: &[Entry]| {
+            lines.push(String::new());
+            lines.push(format!("### {title}"));
+            lines.extend(entries.iter().map(Entry::render));
+        };
+        if !self.breaking.is_empty() {
+            push_section("⚠ BREAKING CHANGES", &self.breaking);
+        }
+        for (section, entries) in &self.sections {
+            push_section(section.title(), entries);
+        }
+
+        let mut text = lines.join("\n");
+        text.push('\n');
+        text
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bump {
+    Major,
+    Minor,
+    Patch,
+}
+
+impl Bump {
+    pub fn reason(self) -> &'static str {
+        match self {
+            Self::Major => "包含破坏性变更",
+            Self::Minor => "包含新功能",
+            Self::Patch => "只有修复和其他改动",
+        }
+    }
+}
+
+/// `MAJOR.MINOR.PATCH` 形式的语义化版本号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    /// 解析 `1.2.3` 或 `v1.2.3`；带有预发布或构建后缀的版本不支持自动升级
+    pub fn parse(text: &str) -> Result<Self> {
+        let core = text.trim().strip_prefix('v').unwrap_or(text.trim());
+        let parts: Vec<&str> = core.split('.').collect();
+        let [major, minor, patch] = parts.as_slice() else {
+            return Err(anyhow!("无法解析版本号: {text}"));
+        };
+        let number = |part: &str| {
+            part.parse::<u64>()
+                .map_err(|_| anyhow!("无法解析版本号: {text}"))
+        };
+        Ok(Version {
+            major: number(major)?,
+            minor: number(minor)?,
+            patch: number(patch)?,
+        })
+    }
+
+    pub fn bump(self, bump: Bump) -> Self {
+        match bump {
+            Bump::Major => Version {
+                major: self.major + 1,
+                minor: 0,
+                patch: 0,
+            },
+            Bump::Minor => Version {
+                minor: self.minor + 1,
+                patch: 0,
+                ..self
+            },
+            Bump::Patch => Version {
+                patch: self.patch + 1,
+                ..self
+            },
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// 标签名对应的版本号，去掉常见的 `v` 前缀
+pub fn version_from_tag(tag: &str) -> String {
+    match tag.strip_prefix('v') {
+        Some(version) if version.starts_with(|c: char| c.is_ascii_digit()) => version.to_string(),
+        _ => tag.to_string(),
+    }
+}
+
+const UNRELEASED_HEADING: &str = "## [Unreleased]";
+
+/// 把新版本段落插入到第一个版本标题之前，已有的内容保持不变。
+/// `## [Unreleased]` 段落始终留在最上面，新版本插入到它后面；
+/// 新的 Unreleased 条目合并进已有的 Unreleased 段落。
+/// 同名的版本已经存在时返回错误，避免重复记录。
+pub fn insert_release(existing: &str, label: &str, section: &str) -> Result<String> {
+    if existing.trim().is_empty() {
+        return Ok(format!("# Changelog\n\n{section}"));
+    }
+
+    let heading = format!("## [{label}]");
+    if heading == UNRELEASED_HEADING
+        && let Some(merged) = merge_unreleased(existing, section)
+    {
+        return Ok(merged);
+    }
+    if existing
+        .lines()
+        .any(|line| line.trim_start().starts_with(&heading))
+    {
+        return Err(anyhow!("CHANGELOG.md 中已经存在 {heading} 段落"));
+    }
+
+    let mut offset = 0;
+    for line in existing.split_inclusive('\n') {
+        if line.starts_with("## ") && !line.starts_with(UNRELEASED_HEADING) {
+            return Ok(format!(
+                "{}{section}\n{}",
+                &existing[..offset],
+                &existing[offset..]
+            ));
+        }
+        offset += line.len();
+    }
+
+    // 还没有任何版本段落，追加到文件末尾
+    let separator = if existing.ends_with("\n\n") {
+        ""
+    } else if existing.ends_with('\n') {
+        "\n"
+    } else {
+        "\n\n"
+    };
+    Ok(format!("{existing}{separator}{section}"))
+}
+
+/// 把 `section` 中的条目合并进已有的 `## [Unreleased]` 段落：条目追加到同名的 `###` 小节末尾，
+/// 已经存在的条目不重复添加，没有的小节追加到段落末尾。没有 Unreleased 段落时返回 `None`
+fn merge_unreleased(existing: &str, section: &str) -> Option<String> {
+    let mut lines: Vec<&str> = existing.lines().collect();
+    let start = lines
+        .iter()
+        .position(|line| line.trim_end() == UNRELEASED_HEADING)?;
+    let mut end = lines[start + 1..]
+        .iter()
+        .position(|line| line.starts_with("## "))
+        .map_or(lines.len(), |index| start + 1 + index);
+
+    // 新段落按 `###` 小节分组，标题行本身跳过
+    let mut subsections: Vec<(&str, Vec<&str>)> = Vec::new();
+    for line in section.lines().skip(1) {
+        if line.starts_with("### ") {
+            subsections.push((line, Vec::new()));
+        } else if !line.trim().is_empty()
+            && let Some((_, entries)) = subsections.last_mut()
+        {
+            entries.push(line);
+        }
+    }
+
+    for (title, entries) in subsections {
+        let block = &lines[start + 1..end];
+        let missing: Vec<&str> = entries
+            .into_iter()
+            .filter(|entry| !block.contains(entry))
+            .collect();
+        if missing.is_empty() {
+            continue;
+        }
+        // 插入位置跳过小节 (或整个段落) 末尾的空行
+        let (insert_at, added) = match block.iter().position(|line| *line == title) {
+            Some(index) => {
+                let title_at = start + 1 + index;
+                let subsection_end = lines[title_at + 1..end]
+                    .iter()
+                    .position(|line| line.starts_with("### "))
+                    .map_or(end, |index| title_at + 1 + index);
+                (subsection_end, missing)
+            }
+            None => {
+                let mut added = vec!["", title];
+                added.extend(missing);
+                (end, added)
+            }
+        };
+        let mut insert_at = insert_at;
+        while insert_at > start + 1 && lines[insert_at - 1].trim().is_empty() {
+            insert_at -= 1;
+        }
+        end += added.len();
+        lines.splice(insert_at..insert_at, added);
+    }
+
+    let mut merged = lines.join("\n");
+    merged.push('\n');
+    Some(merged)
+}
@@ -1,48 +1,206 @@
-use crate::git;
-use crate::history::get_history_dir;
-use anyhow::{Context, Result};
-use tokio::fs;
-
-pub async fn archive_commit_message(project_name: &str, message: &str) -> Result<()> {
-    let project_history_dir = get_history_dir().await?.join(project_name);
-    if !project_history_dir.exists() {
-        fs::create_dir_all(&project_history_dir)
+use crate::git_backend::{self, CommitInfo, GitBackend, LogFilter, RepoContext};
+use crate::history::{self, CommitRecord, Trailer};
+use anyhow::{Context, Result, anyhow};
+use chrono::Local;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use tokio::io::AsyncReadExt;
+
+/// 把一个提交转换成历史记录，附带文件统计和提交上的 git notes
+pub async fn build_record(
+    backend: &dyn GitBackend,
+    repo: &RepoContext,
+    commit: CommitInfo,
+    branch: Option<String>,
+) -> Result<CommitRecord> {
+    let files = backend.commit_stats(&commit.sha).await?;
+    let notes = backend.note("refs/notes/commits", &commit.sha).await?;
+    let patch_id = backend.patch_id(&commit.sha).await?;
+    Ok(CommitRecord {
+        repo_path: Some(repo.toplevel.clone()),
+        project: repo.project_name(),
+        branch,
+        author_name: commit.author_name,
+        author_email: commit.author_email,
+        authored_at: Some(history::local_time(commit.timestamp)),
+        committed_at: history::local_time(commit.commit_timestamp),
+        archived_at: Local::now().fixed_offset(),
+        trailers: Trailer::parse_all(&commit.message),
+        message: commit.message,
+        sha: Some(commit.sha),
+        patch_id,
+        files,
+        notes,
+    })
+}
+
+/// 归档 HEAD 指向的提交 (`post-commit` 钩子)。`rewritten` 为 true 时改为从 stdin 读取
+/// `post-rewrite` 钩子给出的 `<旧 sha> <新 sha>` 行，用新的提交替换已经归档的旧提交
+pub async fn handle_archive(rewritten: bool) -> Result<()> {
+    let repo = git_backend::repo_context()
+        .await
+        .context("无法获取用于归档的项目名称。")?;
+    let backend = git_backend::backend().await?;
+    if rewritten {
+        let mut input = String::new();
+        tokio::io::stdin()
+            .read_to_string(&mut input)
             .await
-            .context("Failed to create project history directory")?;
+            .context("无法读取 post-rewrite 钩子的输入。")?;
+        return archive_rewritten(backend.as_ref(), repo, &input).await;
     }
 
-    let date_str = chrono::Local::now().format("%Y-%m-%d").to_string();
-    let file_path = project_history_dir.join(format!("{date_str}.md"));
+    let last_commit = backend
+        .log(1)
+        .await?
+        .into_iter()
+        .next()
+        .context("无法获取用于归档的最后一条提交信息。")?;
 
-    let mut existing_content = if file_path.exists() {
-        fs::read_to_string(&file_path)
-            .await
-            .context("Failed to read existing history file")?
-    } else {
-        String::new()
-    };
+    let branch = backend.current_branch().await?;
+    let record = build_record(backend.as_ref(), repo, last_commit, branch).await?;
+    history::save_record(record)
+        .await
+        .context("无法归档提交信息。")?;
+    Ok(())
+}
+
+async fn archive_rewritten(
+    backend: &dyn GitBackend,
+    repo: &RepoContext,
+    input: &str,
+) -> Result<()> {
+    let project = repo.project_name();
+    let archived = history::load_records(&project).await?;
+    let branch = backend.current_branch().await?;
 
-    if !existing_content.is_empty() {
-        existing_content.push_str("\n\n---\n\n");
+    let mut rewritten = Vec::new();
+    for line in input.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(old_sha), Some(new_sha)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        // 只替换已经归档的提交，例如 rebase 时从上游拿到的提交不会被加进来
+        let Some(old_record) = archived
+            .iter()
+            .find(|record| record.sha.as_deref() == Some(old_sha))
+        else {
+            continue;
+        };
+        let commit = backend.commit(new_sha).await?;
+        let branch = branch.clone().or_else(|| old_record.branch.clone());
+        let record = build_record(backend, repo, commit, branch).await?;
+        rewritten.push((old_sha.to_string(), record));
     }
-    existing_content.push_str(message);
 
-    fs::write(file_path, existing_content)
+    if rewritten.is_empty() {
+        return Ok(());
+    }
+    history::replace_rewritten(&project, rewritten)
         .await
-        .context("Failed to write to history file")?;
+        .context("无法更新被改写的提交。")
+}
+
+/// 从 git log 导入 `repo` 所有本地分支上的历史提交，已经归档的 sha 会被跳过。
+/// `author` 为 `me` 时只导入这个仓库的 `user.email` 的提交
+pub async fn handle_backfill(
+    backend: &dyn GitBackend,
+    repo: &RepoContext,
+    since: Option<&str>,
+    author: Option<&str>,
+) -> Result<()> {
+    let filter = LogFilter {
+        since: since.map(parse_since).transpose()?,
+        author: match author {
+            Some("me") => {
+                let email = backend
+                    .config_value("user.email")
+                    .await?
+                    .context("没有设置 user.email，无法识别 --author me。")?;
+                Some(format!("<{email}>"))
+            }
+            author => author.map(String::from),
+        },
+    };
 
+    let project = repo.project_name();
+    let archived: HashSet<String> = history::load_records(&project)
+        .await?
+        .into_iter()
+        .filter_map(|record| record.sha)
+        .collect();
+    let commits = backend.branch_commits(&filter).await?;
+    let total = commits.len();
+    // 按从旧到新的顺序导入，与钩子归档的顺序一致
+    let pending: Vec<_> = commits
+        .into_iter()
+        .rev()
+        .filter(|entry| !archived.contains(&entry.commit.sha))
+        .collect();
+
+    let progress_bar = ProgressBar::new(pending.len() as u64);
+    progress_bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} 正在导入 {pos}/{len} 条提交...")
+            .unwrap(),
+    );
+    let mut records = Vec::new();
+    for entry in pending {
+        records.push(build_record(backend, repo, entry.commit, entry.branch).await?);
+        progress_bar.inc(1);
+    }
+    progress_bar.finish_and_clear();
+
+    let imported = records.len();
+    if imported > 0 {
+        history::save_records(&project, records)
+            .await
+            .context("无法保存导入的提交。")?;
+    }
+    println!(
+        "✅ 已从 {project} 导入 {imported} 条提交，跳过 {} 条已归档的提交。",
+        total - imported
+    );
     Ok(())
 }
 
-pub async fn handle_archive() -> Result<()> {
-    let project_name = git::get_git_repo_name()
-        .await
-        .context("无法获取用于归档的项目名称。")?;
-    let commit_message = git::get_last_commit_message()
-        .await
-        .context("无法获取用于归档的最后一条提交信息。")?;
-    archive_commit_message(&project_name, &commit_message)
-        .await
-        .context("无法归档提交信息。")?;
-    Ok(())
+/// 依次导入多个仓库。每个仓库使用各自的 backend 和仓库位置，一个仓库失败不影响其他仓库
+pub async fn backfill_repos(
+    repos: &[PathBuf],
+    since: Option<&str>,
+    author: Option<&str>,
+) -> Result<()> {
+    let mut failed = Vec::new();
+    for dir in repos {
+        let result = async {
+            let (backend, repo) = git_backend::open(dir)
+                .await
+                .with_context(|| format!("无法打开仓库: {}", dir.display()))?;
+            handle_backfill(backend.as_ref(), &repo, since, author).await
+        }
+        .await;
+        if let Err(e) = result {
+            eprintln!("❌ {}: {e:#}", dir.display());
+            failed.push(dir.display().to_string());
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("以下仓库导入失败: {}", failed.join(", ")))
+    }
+}
+
+/// `--since` 的日期，从当天的 00:00 开始
+fn parse_since(since: &str) -> Result<i64> {
+    let date = dateparser::parse(since)
+        .map_err(|_| anyhow!("无法解析日期: {since}"))?
+        .with_timezone(&Local)
+        .date_naive();
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|time| time.and_local_timezone(Local).earliest())
+        .map(|time| time.timestamp())
+        .with_context(|| format!("无法解析日期: {since}"))
 }
@@ -0,0 +1,108 @@
+//! src/commands/changelog.rs
+
+use crate::changelog::{self, Release};
+use crate::config;
+use crate::git;
+use crate::git_backend;
+use crate::llm::polish_changelog;
+use crate::llm::secrets::SecretGuard;
+use anyhow::{Context, Result, anyhow};
+use colored::Colorize;
+
+const CHANGELOG_FILE: &str = "CHANGELOG.md";
+
+pub async fn handle_changelog(
+    from: Option<String>,
+    to: String,
+    polish: bool,
+    write: bool,
+) -> Result<()> {
+    if !git::check_is_git_repo().await {
+        return Err(anyhow!("当前目录不是一个有效的 Git 仓库。"));
+    }
+
+    let backend = git_backend::backend().await?;
+
+    // `to` 本身带有标签时，这次生成的就是该标签对应的版本，起点要从它之前的标签算起
+    let tag_at_to = match backend.latest_tag(&to).await? {
+        Some(tag) if backend.commits_between(Some(&tag), &to).await?.is_empty() => Some(tag),
+        _ => None,
+    };
+    let from = match from {
+        Some(from) => Some(from),
+        None if tag_at_to.is_some() => backend.latest_tag(&format!("{to}^{{commit}}^")).await?,
+        None => backend.latest_tag(&to).await?,
+    };
+
+    let range = match &from {
+        Some(from) => format!("{from}..{to}"),
+        None => to.clone(),
+    };
+    let commits = backend
+        .commits_between(from.as_deref(), &to)
+        .await
+        .with_context(|| format!("无法读取 {range} 中的提交"))?;
+    if commits.is_empty() {
+        eprintln!("{}", format!("{range} 中没有提交。").yellow());
+        return Ok(());
+    }
+
+    let version = tag_at_to.as_deref().map(changelog::version_from_tag);
+    // 已发布的版本使用最新一个提交的日期，Unreleased 不写日期
+    let date = version.as_ref().and_then(|_| {
+        commits
+            .iter()
+            .map(|commit| commit.timestamp)
+            .max()
+            .and_then(|timestamp| chrono::DateTime::from_timestamp(timestamp, 0))
+            .map(|time| time.with_timezone(&chrono::Local).date_naive())
+    });
+    let release = Release::from_commits(version, date, &commits);
+
+    eprintln!("{}", format!("📋 {range}: {} 个提交", commits.len()).cyan());
+    if release.skipped > 0 {
+        eprintln!(
+            "{}",
+            format!(
+                "ℹ️  跳过了 {} 个提交 (不是 Conventional Commits 格式，或 docs/chore/test 等不面向使用者的类型)",
+                release.skipped
+            )
+            .dimmed()
+        );
+    }
+    if release.is_empty() {
+        eprintln!("{}", "没有可以写入变更日志的提交。".yellow());
+        return Ok(());
+    }
+
+    let mut section = release.render();
+    if polish {
+        // 提交信息会发送给 LLM，先处理其中的密钥
+        let draft = SecretGuard::load().await?.protect("变更日志", &section)?;
+        let llm_client = config::get_llm_client().await?;
+        section = polish_changelog(llm_client.as_client(), &draft).await?;
+        section = format!("{}\n", section.trim_end());
+    }
+
+    if !write {
+        print!("{section}");
+        return Ok(());
+    }
+
+    let path = git::get_repo_root().await?.join(CHANGELOG_FILE);
+    let existing = match tokio::fs::read_to_string(&path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("无法读取 {}", path.display())),
+    };
+    let updated = changelog::insert_release(&existing, release.label(), &section)?;
+    tokio::fs::write(&path, updated)
+        .await
+        .with_context(|| format!("无法写入文件: {}", path.display()))?;
+    eprintln!(
+        "{}",
+        format!("✅ 已将 [{}] 写入 {CHANGELOG_FILE}", release.label()).green()
+    );
+
+    Ok(())
+}
@@ -1,23 +1,39 @@
 use crate::commands::install_hook::{check_hook_status, install_post_commit_hook, HookStatus};
-use crate::config;
+use crate::config::{self, IssueConfig};
 use crate::git;
-use crate::llm::generate_commit_message;
+use crate::git_backend::{DiffScope, DiffSource};
+use crate::issue;
+use crate::llm::conversation::{Conversation, Message};
+use crate::llm::guard::Fence;
+use crate::llm::secrets::SecretGuard;
+use crate::llm::{chat_task, generate_commit_message};
 
 use anyhow;
 use anyhow::Context;
 use colored::Colorize;
 use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
 
-async fn prompt_for_metadata() -> anyhow::Result<String> {
+/// `branch_keys` 是从分支名中提取的工单号，作为输入框的初始值
+async fn prompt_for_metadata(
+    issue_config: &IssueConfig,
+    branch_keys: &[String],
+) -> anyhow::Result<String> {
     let mut footer = String::new();
 
     let issue: String = Input::with_theme(&ColorfulTheme::default())
-        .with_prompt("关联的 Issue ID 是什么？(选填, e.g., PROJ-123)")
+        .with_prompt("关联的 Issue ID 是什么？(选填, 多个用逗号分隔, e.g., PROJ-123)")
+        .with_initial_text(branch_keys.join(", "))
         .allow_empty(true)
         .interact_text()?;
 
-    if !issue.trim().is_empty() {
-        footer.push_str(&format!("\nIssue: {}", issue.trim()));
+    let keys: Vec<String> = issue
+        .split([',', ' '])
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(String::from)
+        .collect();
+    for trailer in issue::format_trailers(issue_config, &keys) {
+        footer.push_str(&format!("\n{trailer}"));
     }
 
     let risk_levels = &["low", "medium", "high"];
@@ -47,21 +63,56 @@ async fn prompt_for_metadata() -> anyhow::Result<String> {
     Ok(footer)
 }
 
+const REFINE_SYSTEM_PROMPT: &str = "你是一个专业的Git提交信息助手，擅长根据用户反馈改进提交信息。每次都根据代码变更和用户的全部反馈改进提交信息，保持简洁明了，符合conventional commits格式。只返回改进后的提交信息，不要添加额外的解释。";
+
+/// 开启一段改进对话：diff 只在开头发送一次，之后每轮只追加反馈和回答。
+fn start_refine_conversation(formatted_diff: &str, commit_message: &str) -> Conversation {
+    let fence = Fence::new();
+    let mut conversation = Conversation::new(
+        fence.harden_system_prompt(REFINE_SYSTEM_PROMPT),
+        format!(
+            "代码变更内容：\n{}\n\n请为这些变更编写提交信息。",
+            fence.wrap(formatted_diff)
+        ),
+    );
+    conversation.push(Message::assistant(commit_message));
+    conversation
+}
+
 pub async fn handle_commit(
     all: bool,
     structured: bool,
     no_edit: bool,
+    print: bool,
+    scope: DiffScope,
 ) -> anyhow::Result<()> {
     if !git::check_is_git_repo().await {
         eprintln!("{}", "错误: 当前目录不是一个有效的 Git 仓库。".red());
         return Ok(());
     }
 
-    if !no_edit {
+    if let DiffSource::Range(range) = &scope.source
+        && !range.contains("..")
+    {
+        return Err(anyhow::anyhow!(
+            "--range 需要 A..B 形式的提交区间，例如 main..feature"
+        ));
+    }
+    // `git diff A..B` 比较的是两个端点，A 前进之后会把 A 的新改动反向混进来；
+    // squash merge 只带入 B 自己的改动，所以改为从合并基准开始比较 (`A...B`)
+    let scope = match scope.source {
+        DiffSource::Range(range) if !range.contains("...") => DiffScope {
+            source: DiffSource::Range(range.replacen("..", "...", 1)),
+            ..scope
+        },
+        source => DiffScope { source, ..scope },
+    };
+
+    if !no_edit && !print {
         match check_hook_status().await? {
             HookStatus::NotInstalled => {
                 if Confirm::with_theme(&ColorfulTheme::default())
-                    .with_prompt("检测到您尚未安装 matecode 的 post-commit 钩子，它能帮助自动记录提交历史以生成报告。是否立即为您安装？")
+                    .with_prompt("检测到您尚未安装 matecode 的 post-commit 和 post-rewrite 钩子，它们能帮助自动记录提交历史以生成报告。是否立即为您安装？")
                     .default(true)
                     .interact()?
                 {
@@ -72,7 +123,7 @@ pub async fn handle_commit(
             }
             HookStatus::InstalledByOther => {
                 if Confirm::with_theme(&ColorfulTheme::default())
-                    .with_prompt("检测到已存在一个自定义的 post-commit 钩子。是否要将 `matecode archive` 命令添加到现有钩子中？")
+                    .with_prompt("检测到已存在自定义的 post-commit 或 post-rewrite 钩子。是否要将 `matecode archive` 命令添加到现有钩子中？")
                     .default(true)
                     .interact()?
                 {
@@ -100,22 +151,51 @@ pub async fn handle_commit(
         }
     }
 
-    let diff = git::get_staged_diff()
-        .await
-        .context("无法获取暂存的git diff")?;
+    let config = config::load_config().await?;
+    let llm_client = crate::llm::create_llm_client(&config)?;
+    let diff = git::get_diff(
+        &scope,
+        llm_client.as_client().model_config(),
+        &config.context,
+    )
+    .await
+    .with_context(|| format!("无法获取{}", scope.describe()))?;
 
     if diff.is_empty() {
-        println!("{}", "没有发现暂存的修改.".green());
+        if print {
+            // stdout 只用来输出提交信息
+            eprintln!("{}", format!("{}为空.", scope.describe()).yellow());
+        } else {
+            println!("{}", "没有发现暂存的修改.".green());
+        }
         return Ok(());
     }
 
-    let llm_client = config::get_llm_client().await?;
-    let formatted_diff = git::format_diff_content("staged_changes.diff", &diff);
-    let mut commit_message = generate_commit_message(llm_client.as_client(), &formatted_diff).await?;
+    // 密钥在离开本机之前就要处理掉，后续的改进对话也只使用处理后的 diff
+    let diff = SecretGuard::load().await?.protect(&scope.describe(), &diff)?;
+
+    let issue_config = config.issue;
+    let branch_keys = issue::branch_issue_keys(&issue_config).await?;
+    // 结构化模式下由用户确认工单号，其余情况自动追加分支中的工单号
+    let trailers = if issue_config.auto_trailer && !structured {
+        issue::format_trailers(&issue_config, &branch_keys)
+    } else {
+        Vec::new()
+    };
+
+    let mut commit_message =
+        generate_commit_message(llm_client.as_client(), &diff, &config.context).await?;
     commit_message = commit_message.replace('`', "'");
 
+    // 只输出提交信息，供脚本使用
+    if print {
+        println!("{}", issue::append_trailers(&commit_message, &trailers));
+        return Ok(());
+    }
+
     // If in non-interactive mode (for tests), commit directly and exit.
     if no_edit {
+        let commit_message = issue::append_trailers(&commit_message, &trailers);
         println!("{}", commit_message.cyan());
         git::run_git_command(&["commit", "-m", &commit_message])
             .await
@@ -124,9 +204,20 @@ pub async fn handle_commit(
         return Ok(());
     }
 
+    let formatted_diff = git::render_diff_for_prompt(&diff).await?;
+
+    let model_config = llm_client.as_client().model_config();
+    let conversation_budget = model_config
+        .max_tokens
+        .saturating_sub(model_config.reserved_tokens);
+    let mut conversation: Option<Conversation> = None;
+
     loop {
         println!("\n{}\n", "=".repeat(60));
-        println!("{}", commit_message.cyan());
+        println!(
+            "{}",
+            issue::append_trailers(&commit_message, &trailers).cyan()
+        );
         println!("{}\n", "=".repeat(60));
 
         let options = &["✅ 直接提交", "🔄 重新生成", "💬 AI对话改进", "❌ 退出"];
@@ -139,11 +230,11 @@ pub async fn handle_commit(
 
         match selection {
             0 => {
-                let mut final_commit_message = commit_message;
+                let mut final_commit_message = issue::append_trailers(&commit_message, &trailers);
                 if structured {
-                    let metadata_footer = prompt_for_metadata().await?;
+                    let metadata_footer = prompt_for_metadata(&issue_config, &branch_keys).await?;
                     if !metadata_footer.is_empty() {
-                        final_commit_message.push_str("\n");
+                        final_commit_message.push('\n');
                         final_commit_message.push_str(&metadata_footer);
                     }
                 }
@@ -155,12 +246,16 @@ pub async fn handle_commit(
             }
             1 => {
                 println!("🔄 好的，正在为您重新生成...");
-                commit_message = generate_commit_message(llm_client.as_client(), &formatted_diff).await?;
+                commit_message =
+                    generate_commit_message(llm_client.as_client(), &diff, &config.context).await?;
                 commit_message = commit_message.replace('`', "'");
+                conversation = None;
                 continue;
             }
             2 => {
-                let mut message_for_improvement = commit_message.clone();
+                let conversation = conversation
+                    .get_or_insert_with(|| start_refine_conversation(&formatted_diff, &commit_message));
+                let session_start = conversation.checkpoint();
                 loop {
                     let user_feedback: String = Input::with_theme(&ColorfulTheme::default())
                         .with_prompt("💬 请告诉我您希望如何改进这条提交信息")
@@ -173,22 +268,18 @@ pub async fn handle_commit(
                     }
 
                     println!("🤖 正在根据您的反馈改进提交信息...");
-                    let improvement_prompt = format!(
-                        "用户对以下提交信息有改进建议：\n\n当前提交信息：\n{}\n\n用户反馈：\n{}\n\n代码变更内容：\n{}\n\n请根据用户的反馈和代码变更内容改进提交信息，保持简洁明了，符合conventional commits格式。只返回改进后的提交信息，不要添加额外的解释。",
-                        message_for_improvement, user_feedback, formatted_diff
-                    );
-
-                    match llm_client
-                        .as_client()
-                        .call(
-                            "你是一个专业的Git提交信息助手，擅长根据用户反馈改进提交信息。",
-                            &improvement_prompt,
-                        )
-                        .await
-                    {
+                    let round_start = conversation.checkpoint();
+                    conversation.push(Message::user(user_feedback.trim()));
+
+                    // 只截断发送出去的副本，完整的历史保留在本地
+                    let mut request = conversation.clone();
+                    request.fit_to_budget(conversation_budget);
+
+                    match chat_task(llm_client.as_client(), "refine", request.messages()).await {
                         Ok(improved_message) => {
                             let final_improved_message =
                                 improved_message.replace('`', "'").trim().to_string();
+                            conversation.push(Message::assistant(final_improved_message.clone()));
 
                             println!("\n{}", "=".repeat(60));
                             println!("{}", "改进后的提交信息:".green());
@@ -211,11 +302,11 @@ pub async fn handle_commit(
                                     break;
                                 }
                                 1 => {
-                                    message_for_improvement = final_improved_message;
                                     println!("🔄 好的，请继续告诉我您的改进建议：");
                                     continue;
                                 }
                                 2 => {
+                                    conversation.truncate(session_start);
                                     println!("↩️ 已放弃本次改进，返回主菜单。");
                                     break;
                                 }
@@ -223,6 +314,7 @@ pub async fn handle_commit(
                             }
                         }
                         Err(e) => {
+                            conversation.truncate(round_start);
                             println!("❌ 改进提交信息时出错: {}", e);
                             if !Confirm::with_theme(&ColorfulTheme::default())
                                 .with_prompt("是否重试？")
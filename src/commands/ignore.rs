@@ -0,0 +1,32 @@
+//! src/commands/ignore.rs
+
+use crate::ignore_rules::IgnoreRules;
+use anyhow::Result;
+use colored::Colorize;
+
+/// 说明某个路径是否会被 `.matecode-ignore` 忽略，以及是哪条规则决定的。
+pub async fn handle_ignore_check(path: &str) -> Result<()> {
+    let rules = IgnoreRules::load().await?;
+    let absolute = std::env::current_dir()?.canonicalize()?.join(path);
+    let is_dir = absolute.is_dir();
+
+    let Some(rule) = rules.explain(&absolute, is_dir) else {
+        println!("{} {} 未命中任何忽略规则", "✅".green(), path);
+        return Ok(());
+    };
+
+    if rule.whitelist {
+        println!("{} {} 被规则显式保留", "✅".green(), path);
+    } else {
+        println!("{} {} 会被忽略", "🚫".red(), path);
+    }
+    println!("   规则: {}", rule.pattern.cyan());
+    if let Some(source) = &rule.source {
+        let location = match rule.line {
+            Some(line) => format!("{}:{}", source.display(), line),
+            None => source.display().to_string(),
+        };
+        println!("   来源: {location}");
+    }
+    Ok(())
+}
@@ -1,6 +1,6 @@
 //! src/hook.rs
 
-use crate::git::run_git_command;
+use crate::git_backend;
 use anyhow::{Context, Result};
 use std::path::PathBuf;
 use tokio::fs;
@@ -12,7 +12,27 @@ pub enum HookStatus {
     InstalledByOther,
 }
 
-const HOOK_CONTENT: &str = r#"#!/bin/bash
+/// matecode 需要的钩子：钩子名称、要运行的命令和新建时的脚本内容
+struct Hook {
+    name: &'static str,
+    command: &'static str,
+    content: &'static str,
+}
+
+const HOOKS: &[Hook] = &[
+    Hook {
+        name: "post-commit",
+        command: "matecode archive",
+        content: POST_COMMIT_CONTENT,
+    },
+    Hook {
+        name: "post-rewrite",
+        command: "matecode archive --rewritten",
+        content: POST_REWRITE_CONTENT,
+    },
+];
+
+const POST_COMMIT_CONTENT: &str = r#"#!/bin/bash
 # Post-commit hook for matecode
 # This hook archives the commit message for later use in reports
 
@@ -24,31 +44,52 @@ COMMIT_MESSAGE=$(git log -1 --pretty=%B)
 matecode archive
 "#;
 
-async fn get_hook_path() -> Result<PathBuf> {
-    let git_dir_output = run_git_command(&["rev-parse", "--git-dir"]).await?;
-    let git_dir = git_dir_output.trim();
-    let git_dir_path = PathBuf::from(git_dir);
-    Ok(git_dir_path.join("hooks").join("post-commit"))
+const POST_REWRITE_CONTENT: &str = r#"#!/bin/bash
+# Post-rewrite hook for matecode
+# After `git commit --amend` or `git rebase`, git writes "<old-sha> <new-sha>" lines to stdin;
+# matecode replaces the archived old commits with the rewritten ones
+
+matecode archive --rewritten
+"#;
+
+async fn get_hook_path(name: &str) -> Result<PathBuf> {
+    let hooks_dir = git_backend::repo_context().await?.hooks_dir().await?;
+    Ok(hooks_dir.join(name))
 }
 
+/// 所有钩子都包含 matecode 的命令时为 `InstalledByUs`；
+/// 有钩子已经存在但不包含 matecode 的命令时为 `InstalledByOther`
 pub async fn check_hook_status() -> Result<HookStatus> {
-    let hook_path = get_hook_path().await?;
-    if !hook_path.exists() {
-        return Ok(HookStatus::NotInstalled);
-    }
+    let mut status = HookStatus::InstalledByUs;
+    for hook in HOOKS {
+        let hook_path = get_hook_path(hook.name).await?;
+        if !hook_path.exists() {
+            if status == HookStatus::InstalledByUs {
+                status = HookStatus::NotInstalled;
+            }
+            continue;
+        }
 
-    let content = fs::read_to_string(&hook_path).await?;
+        let content = fs::read_to_string(&hook_path).await?;
 
-    // 检查是否包含 matecode archive 命令
-    if content.contains("matecode archive") {
-        Ok(HookStatus::InstalledByUs)
-    } else {
-        Ok(HookStatus::InstalledByOther)
+        // 检查是否包含 matecode archive 命令
+        if !content.contains(hook.command) {
+            status = HookStatus::InstalledByOther;
+        }
     }
+    Ok(status)
 }
 
+/// 安装 post-commit 和 post-rewrite 钩子
 pub async fn install_post_commit_hook() -> Result<()> {
-    let hook_path = get_hook_path().await?;
+    for hook in HOOKS {
+        install_hook(hook).await?;
+    }
+    Ok(())
+}
+
+async fn install_hook(hook: &Hook) -> Result<()> {
+    let hook_path = get_hook_path(hook.name).await?;
     let hooks_dir = hook_path
         .parent()
         .context("Failed to get hooks directory from path")?;
@@ -64,8 +105,8 @@ pub async fn install_post_commit_hook() -> Result<()> {
         let existing_content = fs::read_to_string(&hook_path).await?;
 
         // 检查是否已经包含 matecode archive 命令
-        if existing_content.contains("matecode archive") {
-            println!("✅ Post-commit 钩子已包含 matecode archive 命令。");
+        if existing_content.contains(hook.command) {
+            println!("✅ {} 钩子已包含 {} 命令。", hook.name, hook.command);
             return Ok(());
         }
 
@@ -74,19 +115,22 @@ pub async fn install_post_commit_hook() -> Result<()> {
         if !new_content.ends_with('\n') {
             new_content.push('\n');
         }
-        new_content.push_str("\n# Added by matecode\nmatecode archive\n");
+        new_content.push_str(&format!("\n# Added by matecode\n{}\n", hook.command));
         fs::write(&hook_path, new_content)
             .await
-            .context("Failed to append to post-commit hook")?;
-        println!("✅ 已将 matecode archive 命令添加到现有的 post-commit 钩子中。");
+            .with_context(|| format!("Failed to append to {} hook", hook.name))?;
+        println!(
+            "✅ 已将 {} 命令添加到现有的 {} 钩子中。",
+            hook.command, hook.name
+        );
         return Ok(());
     }
 
     // 创建新的钩子文件
-    let hook_script = HOOK_CONTENT.replace("\r\n", "\n");
+    let hook_script = hook.content.replace("\r\n", "\n");
     fs::write(&hook_path, hook_script)
         .await
-        .context("Failed to write post-commit hook")?;
+        .with_context(|| format!("Failed to write {} hook", hook.name))?;
 
     #[cfg(unix)]
     {
@@ -98,6 +142,10 @@ pub async fn install_post_commit_hook() -> Result<()> {
             .context("Failed to set hook permissions")?;
     }
 
-    println!("✅ Post-commit 钩子安装成功，位置: {}", hook_path.display());
+    println!(
+        "✅ {} 钩子安装成功，位置: {}",
+        hook.name,
+        hook_path.display()
+    );
     Ok(())
 }
@@ -1,15 +1,24 @@
 pub mod archive;
+pub mod changelog;
 pub mod commit;
+pub mod ignore;
 pub mod init;
 pub mod install_hook;
+pub mod pr;
+pub mod release;
 pub mod report;
 pub mod understand;
 
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
+    /// 在指定目录中运行，如同先切换到该目录 (与 `git -C` 相同)；`archive --backfill` 可以指定多次，依次导入每个仓库
+    #[arg(short = 'C', long = "repo", global = true, value_name = "PATH")]
+    pub repo: Vec<PathBuf>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -20,9 +29,24 @@ pub enum Commands {
     #[command(alias = "i")]
     Init,
 
-    /// 记录每个项目的git信息
-    #[command(hide = true)]
-    Archive,
+    /// 记录每个项目的git信息，由钩子自动调用；使用 --backfill 从 git log 导入历史提交
+    Archive {
+        /// 从 stdin 读取 post-rewrite 钩子给出的 `<旧 sha> <新 sha>`，替换被 amend 或 rebase 改写的记录
+        #[arg(long, conflicts_with = "backfill")]
+        rewritten: bool,
+
+        /// 导入所有本地分支上的历史提交 (不含合并提交)，已经归档的提交会被跳过
+        #[arg(long)]
+        backfill: bool,
+
+        /// 只导入这一天及之后的提交，例如 2024-01-01（需要 --backfill）
+        #[arg(long, value_name = "DATE", requires = "backfill")]
+        since: Option<String>,
+
+        /// 只导入这个作者的提交 (与 `git log --author` 相同)，`me` 表示当前的 user.email（需要 --backfill）
+        #[arg(long, requires = "backfill")]
+        author: Option<String>,
+    },
 
     /// 安装git钩子，搭配archive使用完成自动归档
     InstallHook,
@@ -41,6 +65,59 @@ pub enum Commands {
         /// [测试用] 禁用交互式编辑
         #[arg(long, hide = true)]
         no_edit: bool,
+
+        /// 只输出生成的提交信息，不执行提交，便于在脚本或 `git merge --squash` 中使用
+        #[arg(long, conflicts_with_all = ["all", "structured"])]
+        print: bool,
+
+        /// 描述一个分支相对另一个分支的改动，例如 `main..feature`，从两者的合并基准开始比较（需要 --print）
+        #[arg(long, value_name = "A..B", requires = "print", conflicts_with = "working_tree")]
+        range: Option<String>,
+
+        /// 描述工作区中尚未暂存的改动（需要 --print）
+        #[arg(long, requires = "print")]
+        working_tree: bool,
+
+        /// 只描述匹配的路径，语法与 git pathspec 相同，可以重复使用（需要 --print）
+        #[arg(long, value_name = "PATHSPEC", requires = "print")]
+        pathspec: Vec<String>,
+    },
+
+    /// AI生成当前分支的 Pull Request 标题和描述
+    Pr {
+        /// 目标分支，默认依次尝试 main、master、develop (优先使用 origin 上的同名分支)
+        #[arg(short, long)]
+        base: Option<String>,
+
+        /// 写入文件而不是输出到终端
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// 根据 Conventional Commits 生成 Keep a Changelog 格式的变更日志
+    Changelog {
+        /// 起始标签或提交 (不包含)，默认为 `--to` 之前最近的标签
+        #[arg(long, value_name = "REF")]
+        from: Option<String>,
+
+        /// 结束位置 (包含)，带有标签时作为该版本的变更日志
+        #[arg(long, value_name = "REF", default_value = "HEAD")]
+        to: String,
+
+        /// 使用 AI 润色条目的措辞
+        #[arg(long)]
+        polish: bool,
+
+        /// 把新版本段落插入到仓库根目录的 CHANGELOG.md，已有的内容保持不变
+        #[arg(short, long)]
+        write: bool,
+    },
+
+    /// 根据上一个标签以来的提交建议下一个版本号，生成发布说明并创建标签
+    Release {
+        /// 不再确认，直接更新版本号并创建标签
+        #[arg(short, long)]
+        yes: bool,
     },
 
     /// AI生成工作报告,支持指定起始日期或预定义周期
@@ -59,10 +136,25 @@ pub enum Commands {
         period: Option<String>,
     },
 
+    /// 管理 .matecode-ignore 忽略规则
+    Ignore {
+        #[command(subcommand)]
+        action: IgnoreAction,
+    },
+
     /// AI理解项目结构和功能
     Understand {
         /// 指定要分析的目录路径，默认为当前git仓库根目录
         #[arg(short, long)]
-        dir: Option<String>,
+        dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum IgnoreAction {
+    /// 检查路径是否被忽略，并显示命中的规则
+    Check {
+        /// 要检查的文件或目录路径
+        path: String,
     },
 }
@@ -0,0 +1,152 @@
+//! src/commands/pr.rs
+
+use crate::config;
+use crate::git;
+use crate::git_backend::{self, DiffScope, DiffSource};
+use crate::llm::generate_pr_description;
+use crate::llm::secrets::SecretGuard;
+use anyhow::{Context, Result, anyhow};
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+
+/// 未指定 `--base` 时依次尝试的目标分支
+const DEFAULT_BASES: &[&str] = &["main", "master", "develop"];
+
+/// GitHub 和 GitLab 约定的 PR/MR 模板位置，按优先级排列
+const PR_TEMPLATE_FILES: &[&str] = &[
+    ".github/pull_request_template.md",
+    ".github/PULL_REQUEST_TEMPLATE.md",
+    "pull_request_template.md",
+    "PULL_REQUEST_TEMPLATE.md",
+    "docs/pull_request_template.md",
+    "docs/PULL_REQUEST_TEMPLATE.md",
+    ".gitlab/merge_request_templates/Default.md",
+];
+
+/// 存放多个模板的目录，取文件名排序后的第一个
+const PR_TEMPLATE_DIRS: &[&str] = &[
+    ".github/PULL_REQUEST_TEMPLATE",
+    ".gitlab/merge_request_templates",
+];
+
+pub async fn handle_pr(base: Option<String>, output: Option<PathBuf>) -> Result<()> {
+    if !git::check_is_git_repo().await {
+        return Err(anyhow!("当前目录不是一个有效的 Git 仓库。"));
+    }
+
+    let backend = git_backend::backend().await?;
+    let base = match base {
+        Some(base) => base,
+        None => detect_default_base().await?,
+    };
+    let merge_base = backend
+        .merge_base(&base, "HEAD")
+        .await
+        .with_context(|| format!("无法找到 {base} 与 HEAD 的公共祖先"))?;
+
+    let commits = backend.commits_between(Some(&merge_base), "HEAD").await?;
+    if commits.is_empty() {
+        eprintln!("{}", format!("当前分支相对 {base} 没有新的提交。").yellow());
+        return Ok(());
+    }
+    eprintln!(
+        "{}",
+        format!("📋 {} 个提交，相对于 {base} ({})", commits.len(), &merge_base[..7]).cyan()
+    );
+
+    let scope = DiffScope {
+        source: DiffSource::Range(format!("{merge_base}..HEAD")),
+        pathspec: Vec::new(),
+    };
+    let config = config::load_config().await?;
+    let llm_client = crate::llm::create_llm_client(&config)?;
+    let diff = git::get_diff(
+        &scope,
+        llm_client.as_client().model_config(),
+        &config.context,
+    )
+    .await?;
+
+    // 提交信息和 diff 一样会发送给 LLM，都要先处理密钥
+    let secret_guard = SecretGuard::load().await?;
+    let diff = secret_guard.protect(&scope.describe(), &diff)?;
+    let commits = secret_guard.protect("提交记录", &git::format_commits(&commits))?;
+
+    let toplevel = git::get_repo_root().await?;
+    let pr_template = find_pr_template(&toplevel).await;
+    if let Some((path, _)) = &pr_template {
+        eprintln!(
+            "{}",
+            format!("📝 使用模板: {}", path.strip_prefix(&toplevel).unwrap_or(path).display())
+                .cyan()
+        );
+    }
+
+    let draft = generate_pr_description(
+        llm_client.as_client(),
+        &diff,
+        &base,
+        &commits,
+        pr_template.as_ref().map(|(_, content)| content.as_str()),
+        &config.context,
+    )
+    .await?;
+
+    let rendered = format!("{}\n\n{}\n", draft.title, draft.body);
+    match output {
+        Some(path) => {
+            tokio::fs::write(&path, rendered)
+                .await
+                .with_context(|| format!("无法写入文件: {}", path.display()))?;
+            eprintln!("{}", format!("✅ 已写入 {}", path.display()).green());
+        }
+        None => print!("{rendered}"),
+    }
+
+    Ok(())
+}
+
+/// 优先使用 origin 上的同名分支，它比本地分支更接近 PR 实际的目标
+async fn detect_default_base() -> Result<String> {
+    let backend = git_backend::backend().await?;
+    let remote_branches = backend.refs("refs/remotes/origin/").await?;
+    let local_branches = backend.refs("refs/heads/").await?;
+
+    for name in DEFAULT_BASES {
+        if remote_branches.contains(&format!("refs/remotes/origin/{name}")) {
+            return Ok(format!("origin/{name}"));
+        }
+        if local_branches.contains(&format!("refs/heads/{name}")) {
+            return Ok(name.to_string());
+        }
+    }
+    Err(anyhow!(
+        "无法确定目标分支，请使用 --base 指定，例如 `matecode pr --base main`"
+    ))
+}
+
+async fn find_pr_template(toplevel: &Path) -> Option<(PathBuf, String)> {
+    let mut candidates: Vec<PathBuf> = PR_TEMPLATE_FILES
+        .iter()
+        .map(|file| toplevel.join(file))
+        .collect();
+    for dir in PR_TEMPLATE_DIRS {
+        if let Ok(entries) = std::fs::read_dir(toplevel.join(dir)) {
+            let mut templates: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("md")))
+                .collect();
+            templates.sort();
+            candidates.extend(templates.into_iter().take(1));
+        }
+    }
+
+    for path in candidates {
+        if let Ok(content) = tokio::fs::read_to_string(&path).await
+            && !content.trim().is_empty()
+        {
+            return Some((path, content));
+        }
+    }
+    None
+}
@@ -0,0 +1,155 @@
+//! src/commands/release.rs
+
+use crate::changelog::{Release, Version};
+use crate::config;
+use crate::git;
+use crate::git_backend;
+use crate::llm::generate_release_notes;
+use crate::llm::secrets::SecretGuard;
+use crate::manifest;
+use anyhow::{Context, Result, anyhow};
+use colored::Colorize;
+use dialoguer::{Confirm, theme::ColorfulTheme};
+
+pub async fn handle_release(yes: bool) -> Result<()> {
+    if !git::check_is_git_repo().await {
+        return Err(anyhow!("当前目录不是一个有效的 Git 仓库。"));
+    }
+
+    let backend = git_backend::backend().await?;
+    let previous_tag = backend.latest_tag("HEAD").await?;
+    let commits = backend
+        .commits_between(previous_tag.as_deref(), "HEAD")
+        .await?;
+    if commits.is_empty() {
+        eprintln!(
+            "{}",
+            format!(
+                "自 {} 以来没有新的提交，无需发布。",
+                previous_tag.as_deref().unwrap_or("首个提交")
+            )
+            .yellow()
+        );
+        return Ok(());
+    }
+
+    let root = git::get_repo_root().await?;
+    let manifests = manifest::find_manifests(&root).await?;
+
+    // 以最近的标签为准；还没有发布过时使用清单中的版本号
+    let current = match (&previous_tag, manifests.first()) {
+        (Some(tag), _) => {
+            Version::parse(tag).with_context(|| format!("无法从标签 {tag} 中解析版本号"))?
+        }
+        (None, Some(manifest)) => Version::parse(&manifest.version)
+            .with_context(|| format!("无法解析 {} 中的版本号", manifest.file_name()))?,
+        (None, None) => Version::default(),
+    };
+    let bump = Release::from_commits(None, None, &commits).suggested_bump();
+    let next = current.bump(bump);
+    // 沿用上一个标签的命名习惯
+    let tag = match &previous_tag {
+        Some(previous) if !previous.starts_with('v') => next.to_string(),
+        _ => format!("v{next}"),
+    };
+
+    if backend
+        .refs("refs/tags/")
+        .await?
+        .contains(&format!("refs/tags/{tag}"))
+    {
+        return Err(anyhow!("标签 {tag} 已经存在。"));
+    }
+    // 锁文件中记录的版本号和清单一起更新、一起提交
+    let paths: Vec<&std::path::Path> = manifests
+        .iter()
+        .flat_map(|manifest| manifest.paths())
+        .collect();
+    let files = paths
+        .iter()
+        .filter_map(|path| path.file_name())
+        .map(|name| name.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("、");
+    let manifest_paths: Vec<String> = paths
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    if !manifest_paths.is_empty() {
+        let mut args = vec!["status", "--porcelain", "--"];
+        args.extend(manifest_paths.iter().map(String::as_str));
+        // 发布提交只应该包含版本号的改动
+        if !git::run_git_command(&args).await?.trim().is_empty() {
+            return Err(anyhow!("{files} 有未提交的修改，请先提交后再发布。"));
+        }
+    }
+
+    eprintln!(
+        "{}",
+        format!(
+            "📦 {current} → {next} ({}，自 {} 以来 {} 个提交)",
+            bump.reason(),
+            previous_tag.as_deref().unwrap_or("首个提交"),
+            commits.len()
+        )
+        .cyan()
+    );
+
+    let today = chrono::Local::now().date_naive();
+    let draft = Release::from_commits(Some(next.to_string()), Some(today), &commits).render();
+    let secret_guard = SecretGuard::load().await?;
+    let draft = secret_guard.protect("变更日志", &draft)?;
+    let commit_list = secret_guard.protect("提交记录", &git::format_commits(&commits))?;
+
+    let llm_client = config::get_llm_client().await?;
+    let notes = generate_release_notes(
+        llm_client.as_client(),
+        &tag,
+        previous_tag.as_deref(),
+        &draft,
+        &commit_list,
+    )
+    .await?;
+
+    println!("\n{}\n", "=".repeat(60));
+    println!("{notes}");
+    println!("\n{}\n", "=".repeat(60));
+
+    let question = if files.is_empty() {
+        format!("是否创建附注标签 {tag}？")
+    } else {
+        format!("是否将 {files} 的版本更新为 {next}，提交并创建附注标签 {tag}？")
+    };
+    if !yes
+        && !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(question)
+            .default(true)
+            .interact()?
+    {
+        println!("好的，操作已取消，没有修改任何文件。");
+        return Ok(());
+    }
+
+    if !manifests.is_empty() {
+        for manifest in &manifests {
+            manifest.write_version(&next.to_string()).await?;
+        }
+        let message = format!("chore(release): {tag}");
+        let mut args = vec!["commit", "-m", &message, "--"];
+        args.extend(manifest_paths.iter().map(String::as_str));
+        git::run_git_command(&args)
+            .await
+            .context("无法提交版本号的修改。")?;
+        println!("{}", format!("✅ 已将 {files} 的版本更新为 {next}").green());
+    }
+
+    // 默认的 strip 会把 Markdown 标题当作注释删掉
+    let message = format!("{tag}\n\n{notes}");
+    git::run_git_command(&["tag", "-a", "--cleanup=verbatim", &tag, "-m", &message])
+        .await
+        .with_context(|| format!("无法创建标签 {tag}。"))?;
+    println!("{}", format!("🏷️  已创建标签 {tag}").green());
+    println!("使用 `git push --follow-tags` 推送提交和标签。");
+
+    Ok(())
+}
@@ -34,7 +34,7 @@ async fn generate_report_from_commits(
         .replace("{end_date}", &end_date.to_string())
         .replace("{commits}", &commits_text);
 
-    client.call(&system_prompt, &user_prompt).await
+    crate::llm::call_task(client, "report", &system_prompt, &user_prompt).await
 }
 
 /// 解析预定义的时间周期
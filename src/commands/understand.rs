@@ -2,24 +2,40 @@
 
 use crate::config;
 use crate::git;
-use crate::llm::{parse_prompt_template, LLMClient};
+use crate::git_backend;
+use crate::ignore_rules::IgnoreRules;
+use crate::llm::guard::{self, Fence};
+use crate::llm::secrets::SecretGuard;
+use crate::llm::{call_task, parse_prompt_template, track_progress, LLMClient};
 use anyhow::Result;
 use colored::Colorize;
 use indicatif::{ProgressBar, ProgressStyle};
 use termimad::MadSkin;
 use std::collections::HashMap;
-use std::path::Path;
-use tokio::fs;
+use std::path::{Path, PathBuf};
 
 /// Handles the project understanding process.
-pub async fn handle_understand(_dir: Option<String>) -> Result<()> {
+pub async fn handle_understand(dir: Option<PathBuf>) -> Result<()> {
+    // Like `-C`, enter the directory first so that recent commits, ignore rules
+    // and the repository context all come from the repository it belongs to
+    if let Some(dir) = &dir {
+        std::env::set_current_dir(dir)
+            .map_err(|e| anyhow::anyhow!("无法访问目录 {}: {e}", dir.display()))?;
+    }
+
     // Check if the directory is a git repository
     if !git::check_is_git_repo().await {
         return Err(anyhow::anyhow!("指定的目录不是git仓库"));
     }
 
+    // Analyze the given directory, or the repository root when none is given
+    let root = match dir {
+        Some(_) => std::env::current_dir()?.canonicalize()?,
+        None => git::get_repo_root().await?,
+    };
+
     // Get project information
-    let project_info = collect_project_info().await?;
+    let project_info = collect_project_info(&root).await?;
 
     println!("{}", "🤖 正在分析项目结构...".cyan());
     
@@ -39,47 +55,61 @@ pub async fn handle_understand(_dir: Option<String>) -> Result<()> {
 
 /// Get recent git commits for project context
 async fn get_recent_commits() -> Result<String> {
+    let backend = git_backend::backend().await?;
+
     // Get last 5 commits with their messages and dates
-    let commits = git::run_git_command(&["log", "--oneline", "-5", "--pretty=format:%h %s (%cr)"]).await?;
-    Ok(commits)
+    let lines: Vec<String> = backend
+        .log(5)
+        .await?
+        .iter()
+        .map(|commit| {
+            let date = chrono::DateTime::from_timestamp(commit.commit_timestamp, 0)
+                .map(|time| time.format("%Y-%m-%d").to_string())
+                .unwrap_or_default();
+            format!("{} {} ({})", commit.short_sha(), commit.subject(), date)
+        })
+        .collect();
+    Ok(lines.join("\n"))
 }
 
 /// Collects project information for understanding.
-async fn collect_project_info() -> Result<ProjectInfo> {
+async fn collect_project_info(root: &Path) -> Result<ProjectInfo> {
     // Get recent commits for context
     let recent_commits = get_recent_commits().await.unwrap_or_else(|_| "无法获取提交记录".to_string());
 
     // Scan the actual filesystem structure instead of git files
-    let filtered_files = scan_filesystem_structure().await?;
+    let filtered_files = scan_filesystem_structure(root).await?;
     
     let file_structure = filtered_files.join("\n");
 
     // Read content of all relevant files
+    let secret_guard = SecretGuard::load().await?;
     let mut file_contents = std::collections::HashMap::new();
     for file in &filtered_files {
         // Read content of all relevant files
         if is_relevant_file(file) {
             // Double-check file exists before reading
-            if std::path::Path::new(file).exists() {
+            if root.join(file).exists() {
                 // Read file content with increased limit
-                if let Ok(content) = read_file_content(file).await {
+                if let Ok(content) = read_file_content(&root.join(file)).await {
+                    let content = secret_guard.protect(&format!("文件 {file}"), &content)?;
                     file_contents.insert(file.clone(), content);
                 }
             }
         }
     }
 
-    // Get project name from current directory
-    let project_name = std::env::current_dir()
-        .ok()
-        .and_then(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+    // Get project name from the analyzed directory
+    let project_name = root
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
         .unwrap_or_else(|| "Unknown".to_string());
 
     // Get project type (simplified detection)
-    let project_type = detect_project_type().await;
+    let project_type = detect_project_type(root).await;
 
     // Get tech stack (simplified detection)
-    let tech_stack = detect_tech_stack().await;
+    let tech_stack = detect_tech_stack(root).await;
 
     // Get key features by analyzing actual file contents
     let key_features = analyze_key_features_from_content(&file_contents).await;
@@ -166,19 +196,19 @@ async fn analyze_key_features_from_content(file_contents: &HashMap<String, Strin
 }
 
 /// Detects project type based on actual files in the filesystem.
-async fn detect_project_type() -> String {
+async fn detect_project_type(root: &Path) -> String {
     // Check for actual existing files
-    if Path::new("Cargo.toml").exists() {
+    if root.join("Cargo.toml").exists() {
         "Rust 项目".to_string()
-    } else if Path::new("package.json").exists() {
+    } else if root.join("package.json").exists() {
         "Node.js 项目".to_string()
-    } else if Path::new("requirements.txt").exists() || 
-              Path::new("pyproject.toml").exists() ||
-              Path::new("setup.py").exists() {
+    } else if root.join("requirements.txt").exists() || 
+              root.join("pyproject.toml").exists() ||
+              root.join("setup.py").exists() {
         "Python 项目".to_string()
-    } else if Path::new("pom.xml").exists() {
+    } else if root.join("pom.xml").exists() {
         "Java 项目".to_string()
-    } else if Path::new("go.mod").exists() {
+    } else if root.join("go.mod").exists() {
         "Go 项目".to_string()
     } else {
         "未知类型项目".to_string()
@@ -186,29 +216,29 @@ async fn detect_project_type() -> String {
 }
 
 /// Detects technology stack based on actual files in the filesystem.
-async fn detect_tech_stack() -> String {
+async fn detect_tech_stack(root: &Path) -> String {
     // Check for actual existing files
     let mut tech_stack = Vec::new();
     
-    if Path::new("Cargo.toml").exists() {
+    if root.join("Cargo.toml").exists() {
         tech_stack.push("Rust".to_string());
     }
     
-    if Path::new("package.json").exists() {
+    if root.join("package.json").exists() {
         tech_stack.push("JavaScript/TypeScript".to_string());
     }
     
-    if Path::new("requirements.txt").exists() || 
-       Path::new("pyproject.toml").exists() ||
-       Path::new("setup.py").exists() {
+    if root.join("requirements.txt").exists() || 
+       root.join("pyproject.toml").exists() ||
+       root.join("setup.py").exists() {
         tech_stack.push("Python".to_string());
     }
     
-    if Path::new("pom.xml").exists() {
+    if root.join("pom.xml").exists() {
         tech_stack.push("Java".to_string());
     }
     
-    if Path::new("go.mod").exists() {
+    if root.join("go.mod").exists() {
         tech_stack.push("Go".to_string());
     }
     
@@ -224,6 +254,13 @@ async fn generate_project_understanding(client: &dyn LLMClient, project_info: &P
     let template = config::get_prompt_template("understand").await?;
     let (system_prompt, user_prompt) = parse_prompt_template(&template)?;
 
+    // 文件内容是不可信数据：先在本地扫描可疑指令，再用随机分隔符包裹
+    for (file_path, content) in &project_info.file_contents {
+        guard::warn_if_suspicious(&format!("文件 {file_path}"), content);
+    }
+    let fence = Fence::new();
+    let system_prompt = fence.harden_system_prompt(&system_prompt);
+
     let progress_bar = ProgressBar::new_spinner();
     progress_bar.set_style(
         ProgressStyle::default_spinner()
@@ -231,6 +268,7 @@ async fn generate_project_understanding(client: &dyn LLMClient, project_info: &P
             .unwrap(),
     );
     progress_bar.enable_steady_tick(std::time::Duration::from_millis(100));
+    track_progress(&progress_bar);
 
     // Prepare context variables
     let project_context = format!(
@@ -257,7 +295,7 @@ async fn generate_project_understanding(client: &dyn LLMClient, project_info: &P
     // Format file contents for the prompt
     let mut file_contents_str = String::new();
     for (file_path, content) in &project_info.file_contents {
-        file_contents_str.push_str(&format!("\n文件: {}\n{}\n", file_path, content));
+        file_contents_str.push_str(&format!("\n文件: {}\n{}\n", file_path, fence.wrap(content)));
     }
 
     let final_prompt = user_prompt
@@ -271,13 +309,13 @@ async fn generate_project_understanding(client: &dyn LLMClient, project_info: &P
         .replace("{file_contents}", &file_contents_str);
 
     
-    let understanding = client.call(&system_prompt, &final_prompt).await;
+    let understanding = call_task(client, "understand", &system_prompt, &final_prompt).await;
     progress_bar.finish_with_message("✓ AI analysis complete");
     understanding
 }
 
 /// Reads the content of a file, with a higher limit for better project understanding.
-async fn read_file_content(file_path: &str) -> Result<String> {
+async fn read_file_content(file_path: &Path) -> Result<String> {
     use tokio::fs;
     
     // Read the file content with a higher limit (10,000 characters instead of 2,000)
@@ -294,15 +332,18 @@ async fn read_file_content(file_path: &str) -> Result<String> {
 }
 
 /// Scans the filesystem structure to get actual project files
-async fn scan_filesystem_structure() -> Result<Vec<String>> {
+async fn scan_filesystem_structure(root: &Path) -> Result<Vec<String>> {
+    let rules = IgnoreRules::load().await?;
     let mut files = Vec::new();
-    scan_directory_recursive(".", &mut files, 0, 3)?; // Max depth 3
+    scan_directory_recursive("", root, &rules, &mut files, 0, 3)?; // Max depth 3
     Ok(files)
 }
 
 /// Recursively scans a directory for relevant files
 fn scan_directory_recursive(
     dir_path: &str,
+    base_dir: &Path,
+    rules: &IgnoreRules,
     files: &mut Vec<String>,
     current_depth: usize,
     max_depth: usize,
@@ -311,8 +352,7 @@ fn scan_directory_recursive(
         return Ok(());
     }
 
-    let mut entries = std::fs::read_dir(dir_path)?;
-    while let Some(entry) = entries.next() {
+    for entry in std::fs::read_dir(base_dir.join(dir_path))? {
         let entry = entry?;
         let path = entry.path();
         let file_name = path.file_name()
@@ -333,14 +373,21 @@ fn scan_directory_recursive(
             continue;
         }
 
-        let relative_path = path.strip_prefix(".")
-            .unwrap_or(&path)
-            .to_string_lossy()
-            .to_string();
+        // Paths are relative to the analyzed directory
+        let relative_path = if dir_path.is_empty() {
+            file_name.to_string()
+        } else {
+            format!("{dir_path}/{file_name}")
+        };
+
+        // Skip paths matched by .matecode-ignore
+        if rules.is_ignored(&path, path.is_dir()) {
+            continue;
+        }
 
         if path.is_dir() {
             // Recursively scan subdirectories
-            scan_directory_recursive(&relative_path, files, current_depth + 1, max_depth)?;
+            scan_directory_recursive(&relative_path, base_dir, rules, files, current_depth + 1, max_depth)?;
         } else {
             // Check if it's a relevant file
             if is_relevant_file(&relative_path) {
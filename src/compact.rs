@@ -0,0 +1,340 @@
+//! src/compact.rs
+//!
+//! diff 超出模型预算时，先在本地压缩，尽量用一次请求完成生成，而不是切分成 N+1 次请求。
+//! 按损失从小到大依次进行，每一步之后放得下就停止：
+//! 1. 去掉只有空白变化的 hunk (缩进有语义的文件除外)，锁文件和生成的文件只保留一行统计；
+//! 2. 只有删除的 hunk 折叠成一行，例如 "removed 40 lines from fn old_helper"；
+//! 3. 按重要性 (源码、测试、配置、文档) 排序，从最不重要的文件开始去掉 hunk。
+//!    只有还存在更重要的改动时才去掉，并且每个文件至少保留第一个 hunk，
+//!    只改了文档或配置的提交不会被压缩成几行统计，而是交给切分总结。
+//!
+//! 被压缩的内容在文件头中用 `[compacted]` 行说明。
+
+use crate::git::{FileDiff, estimeate_token_count};
+use crate::ignore_rules;
+use crate::symbols;
+use std::path::Path;
+
+/// 生成的文件常见的路径特征
+const GENERATED_SUFFIXES: &[&str] = &[
+    ".min.js",
+    ".min.css",
+    ".map",
+    ".snap",
+    ".pb.go",
+    "_pb2.py",
+    "_pb2_grpc.py",
+    ".pb.rs",
+    ".g.dart",
+    ".freezed.dart",
+    ".designer.cs",
+];
+const GENERATED_DIRS: &[&str] = &[
+    "dist",
+    "vendor",
+    "node_modules",
+    "generated",
+    "__generated__",
+];
+/// 生成的文件开头常见的标记，只检查前几行新增的内容
+const GENERATED_MARKERS: &[&str] = &[
+    "@generated",
+    "DO NOT EDIT",
+    "Code generated",
+    "auto-generated",
+    "autogenerated",
+];
+const MARKER_SCAN_LINES: usize = 5;
+
+/// 缩进有语义的文件，只有空白变化的 hunk 也可能改变行为，不能去掉
+const INDENT_SENSITIVE_EXTENSIONS: &[&str] = &[
+    "py", "pyi", "yaml", "yml", "mk", "make", "haml", "pug", "jade", "slim", "sass", "styl",
+    "coffee", "nim", "fs", "fsx", "hs", "elm",
+];
+const INDENT_SENSITIVE_NAMES: &[&str] = &["makefile", "gnumakefile", "bsdmakefile"];
+
+const DOC_EXTENSIONS: &[&str] = &["md", "markdown", "rst", "adoc"];
+const DOC_DIRS: &[&str] = &["docs", "doc"];
+const DOC_NAMES: &[&str] = &["readme", "changelog", "license", "contributing"];
+const TEST_DIRS: &[&str] = &["tests", "test", "__tests__", "spec", "testdata", "fixtures"];
+const SOURCE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "pyi", "ts", "tsx", "js", "jsx", "mjs", "cjs", "go", "java", "kt", "kts", "scala",
+    "swift", "c", "h", "cc", "cpp", "hpp", "cs", "rb", "php", "ex", "exs", "dart", "lua", "sh",
+    "vue", "svelte", "sql",
+];
+
+/// 文件的重要性，越靠前越重要
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Priority {
+    Source,
+    Test,
+    Config,
+    Docs,
+}
+
+impl Priority {
+    fn of(path: &str) -> Self {
+        let lower = path.to_lowercase();
+        let path = Path::new(&lower);
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let stem = file_name.split('.').next().unwrap_or_default();
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let dirs: Vec<&str> = lower.split('/').rev().skip(1).collect();
+
+        let is_test = dirs.iter().any(|dir| TEST_DIRS.contains(dir))
+            || stem.starts_with("test_")
+            || stem.ends_with("_test")
+            || stem.ends_with("_spec")
+            || file_name.contains(".test.")
+            || file_name.contains(".spec.")
+            || (extension == "java" && stem.ends_with("test"));
+        if is_test {
+            Priority::Test
+        } else if DOC_NAMES.contains(&stem)
+            || DOC_EXTENSIONS.contains(&extension.as_str())
+            || dirs.iter().any(|dir| DOC_DIRS.contains(dir))
+        {
+            Priority::Docs
+        } else if SOURCE_EXTENSIONS.contains(&extension.as_str()) {
+            Priority::Source
+        } else {
+            Priority::Config
+        }
+    }
+}
+
+/// 依次压缩，直到放进 `token_limit`；全部压缩后仍然放不下时返回 `None`，由调用方切分
+pub fn compact(files: &[FileDiff], token_limit: usize) -> Option<Vec<FileDiff>> {
+    let mut files = strip_noise(files);
+    if total_tokens(&files) <= token_limit {
+        return Some(files);
+    }
+
+    for file in &mut files {
+        collapse_deletions(file);
+    }
+    if total_tokens(&files) <= token_limit {
+        return Some(files);
+    }
+
+    // 重要的文件放在前面；源码中的 hunk 不会被去掉，最重要的一类文件也不会
+    files.sort_by_key(|file| Priority::of(&file.path));
+    let most_important = files
+        .iter()
+        .filter(|file| !file.hunks.is_empty())
+        .map(|file| Priority::of(&file.path))
+        .min()?;
+    let mut candidates: Vec<usize> = (0..files.len())
+        .filter(|&index| {
+            let priority = Priority::of(&files[index].path);
+            priority != Priority::Source && priority > most_important
+        })
+        .collect();
+    candidates.sort_by_key(|&index| {
+        (
+            std::cmp::Reverse(Priority::of(&files[index].path)),
+            std::cmp::Reverse(file_tokens(&files[index])),
+        )
+    });
+    let mut total = total_tokens(&files);
+    for index in candidates {
+        if total <= token_limit {
+            break;
+        }
+        total -= trim_hunks(&mut files[index], total - token_limit);
+    }
+
+    (total_tokens(&files) <= token_limit).then_some(files)
+}
+
+/// 只去掉对理解改动没有帮助的内容：只有空白变化的 hunk，锁文件和生成的文件的具体内容。
+/// 切分之前也会使用
+pub fn strip_noise(files: &[FileDiff]) -> Vec<FileDiff> {
+    files
+        .iter()
+        .map(|file| {
+            let mut file = file.clone();
+            if file.hunks.is_empty() {
+                return file;
+            }
+
+            let kind = if ignore_rules::is_lockfile(&file.path) {
+                Some("lockfile updated")
+            } else if is_generated(&file) {
+                Some("generated file changed")
+            } else {
+                None
+            };
+            if let Some(kind) = kind {
+                let (added, removed) = count_lines(&file.hunks);
+                file.header
+                    .push_str(&format!("[compacted] {kind}, +{added}/-{removed}\n"));
+                file.hunks.clear();
+                return file;
+            }
+
+            if is_indentation_sensitive(&file.path) {
+                return file;
+            }
+            let before = file.hunks.len();
+            file.hunks.retain(|hunk| !is_whitespace_only(hunk));
+            let dropped = before - file.hunks.len();
+            if dropped > 0 {
+                file.header.push_str(&format!(
+                    "[compacted] {dropped} whitespace-only hunk(s) omitted\n"
+                ));
+            }
+            file
+        })
+        .collect()
+}
+
+/// 只有删除的 hunk 只保留 hunk 头和一行描述
+fn collapse_deletions(file: &mut FileDiff) {
+    for hunk in &mut file.hunks {
+        let (added, removed) = count_lines(std::slice::from_ref(hunk));
+        if added > 0 || removed == 0 {
+            continue;
+        }
+
+        let header = hunk.lines().next().unwrap_or_default().to_string();
+        let declarations = symbols::removed_declarations(&file.path, hunk);
+        let description = if !declarations.is_empty() {
+            format!(
+                "removed {removed} lines including {}",
+                declarations.join(", ")
+            )
+        } else if let Some(scope) = symbols::hunk_scope(&file.path, hunk) {
+            format!("removed {removed} lines from {scope}")
+        } else {
+            format!("removed {removed} lines")
+        };
+        let collapsed = format!("{header}\n[compacted] {description}\n");
+        // 很短的 hunk 折叠后反而更长
+        if collapsed.len() < hunk.len() {
+            *hunk = collapsed;
+        }
+    }
+}
+
+/// 从最大的 hunk 开始去掉，直到省下 `needed` 个 token，返回实际省下的数量。
+/// 第一个 hunk 总是保留，让模型知道这个文件改了什么
+fn trim_hunks(file: &mut FileDiff, needed: usize) -> usize {
+    let before = file_tokens(file);
+    let mut order: Vec<usize> = (1..file.hunks.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse(file.hunks[index].len()));
+
+    let mut dropped = Vec::new();
+    let mut saved = 0;
+    for index in order {
+        if saved >= needed {
+            break;
+        }
+        saved += estimeate_token_count(&file.hunks[index]);
+        dropped.push(index);
+    }
+    if dropped.is_empty() {
+        return 0;
+    }
+
+    let removed_hunks: Vec<String> = dropped.iter().map(|&i| file.hunks[i].clone()).collect();
+    let (added, removed) = count_lines(&removed_hunks);
+    let mut index = 0;
+    file.hunks.retain(|_| {
+        let keep = !dropped.contains(&index);
+        index += 1;
+        keep
+    });
+    file.header.push_str(&format!(
+        "[compacted] {} lower-priority hunk(s) omitted, +{added}/-{removed}\n",
+        removed_hunks.len()
+    ));
+    before.saturating_sub(file_tokens(file))
+}
+
+fn is_generated(file: &FileDiff) -> bool {
+    let lower = file.path.to_lowercase();
+    if GENERATED_SUFFIXES
+        .iter()
+        .any(|suffix| lower.ends_with(suffix))
+        || lower
+            .split('/')
+            .rev()
+            .skip(1)
+            .any(|dir| GENERATED_DIRS.contains(&dir))
+    {
+        return true;
+    }
+    file.hunks
+        .iter()
+        .flat_map(|hunk| hunk.lines().skip(1))
+        .filter(|line| line.starts_with('+'))
+        .take(MARKER_SCAN_LINES)
+        .any(|line| GENERATED_MARKERS.iter().any(|marker| line.contains(marker)))
+}
+
+fn is_indentation_sensitive(path: &str) -> bool {
+    let lower = path.to_lowercase();
+    let path = Path::new(&lower);
+    let matches = |name: Option<&std::ffi::OsStr>, list: &[&str]| {
+        name.and_then(|name| name.to_str())
+            .is_some_and(|name| list.contains(&name))
+    };
+    matches(path.extension(), INDENT_SENSITIVE_EXTENSIONS)
+        || matches(path.file_name(), INDENT_SENSITIVE_NAMES)
+}
+
+/// 删除和新增的行去掉首尾空白后逐行相同，例如重新缩进；空行的增减也视为空白变化。
+/// 行内的空白 (例如字符串中的空格) 不忽略
+fn is_whitespace_only(hunk: &str) -> bool {
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    let mut changed = false;
+    for line in hunk.lines().skip(1) {
+        let (lines, code) = if let Some(code) = line.strip_prefix('-') {
+            (&mut removed, code)
+        } else if let Some(code) = line.strip_prefix('+') {
+            (&mut added, code)
+        } else {
+            continue;
+        };
+        changed = true;
+        let code = code.trim();
+        if !code.is_empty() {
+            lines.push(code);
+        }
+    }
+    changed && removed == added
+}
+
+fn count_lines(hunks: &[String]) -> (usize, usize) {
+    let mut added = 0;
+    let mut removed = 0;
+    for line in hunks.iter().flat_map(|hunk| hunk.lines().skip(1)) {
+        if line.starts_with('+') {
+            added += 1;
+        } else if line.starts_with('-') {
+            removed += 1;
+        }
+    }
+    (added, removed)
+}
+
+fn file_tokens(file: &FileDiff) -> usize {
+    estimeate_token_count(&file.header)
+        + file
+            .hunks
+            .iter()
+            .map(|hunk| estimeate_token_count(hunk))
+            .sum::<usize>()
+}
+
+fn total_tokens(files: &[FileDiff]) -> usize {
+    files.iter().map(file_tokens).sum()
+}
@@ -46,6 +46,130 @@ pub struct Config {
     pub language: String,
     /// LLM provider settings.
     pub llm: LLMProviders,
+    /// Secret scanning applied before diffs and file contents leave the machine.
+    #[serde(default)]
+    pub secrets: SecretsConfig,
+    /// How matecode reads repository data.
+    #[serde(default)]
+    pub git_backend: GitBackendKind,
+    /// Surrounding code and history sent along with the diff.
+    #[serde(default)]
+    pub context: ContextConfig,
+    /// Issue keys taken from the branch name and written as commit trailers.
+    #[serde(default)]
+    pub issue: IssueConfig,
+}
+
+/// How much context around the change goes into commit prompts.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ContextConfig {
+    /// Unchanged lines kept around each change, like `git diff -U<n>`.
+    #[serde(default = "default_context_lines")]
+    pub lines: u32,
+    /// Send the whole enclosing function (`git diff -W`) when the diff still fits the model.
+    #[serde(default)]
+    pub function_context: bool,
+    /// Recent commit subjects listed for each affected file, 0 turns it off.
+    #[serde(default)]
+    pub file_history: usize,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        ContextConfig {
+            lines: default_context_lines(),
+            function_context: false,
+            file_history: 0,
+        }
+    }
+}
+
+fn default_context_lines() -> u32 {
+    3
+}
+
+/// How issue keys are found in branch names and written into commit messages.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IssueConfig {
+    /// Regex matched against the current branch; the first capture group is used when present.
+    #[serde(default = "default_issue_pattern")]
+    pub pattern: String,
+    /// Trailer name, e.g. `Issue` or `Refs`.
+    #[serde(default = "default_issue_trailer")]
+    pub trailer: String,
+    /// Trailer value, `{key}` is replaced with the issue key.
+    #[serde(default = "default_issue_format")]
+    pub format: String,
+    /// Keys or key prefixes that are never issue keys, e.g. `UTF` for `fix/UTF-8-decoding`.
+    #[serde(default = "default_issue_exclude")]
+    pub exclude: Vec<String>,
+    /// Append the trailer automatically outside structured mode.
+    #[serde(default = "default_auto_trailer")]
+    pub auto_trailer: bool,
+}
+
+impl Default for IssueConfig {
+    fn default() -> Self {
+        IssueConfig {
+            pattern: default_issue_pattern(),
+            trailer: default_issue_trailer(),
+            format: default_issue_format(),
+            exclude: default_issue_exclude(),
+            auto_trailer: default_auto_trailer(),
+        }
+    }
+}
+
+fn default_issue_pattern() -> String {
+    r"[A-Z][A-Z0-9]+-\d+".to_string()
+}
+
+fn default_issue_trailer() -> String {
+    "Issue".to_string()
+}
+
+fn default_issue_format() -> String {
+    "{key}".to_string()
+}
+
+fn default_issue_exclude() -> Vec<String> {
+    ["UTF", "SHA", "ISO", "CVE", "RFC", "HTTP", "X86", "AES"]
+        .map(String::from)
+        .to_vec()
+}
+
+fn default_auto_trailer() -> bool {
+    true
+}
+
+/// Implementation used for read-only git operations.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GitBackendKind {
+    /// Spawn the `git` executable.
+    #[default]
+    Process,
+    /// Read the repository in-process with gix.
+    Gix,
+}
+
+/// What to do when a likely secret is found in content bound for the LLM.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretsMode {
+    /// Replace each secret with a placeholder and continue.
+    #[default]
+    Redact,
+    /// Refuse to send anything and list the findings.
+    Block,
+    /// Send the content unchanged.
+    Allow,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SecretsConfig {
+    #[serde(default)]
+    pub mode: SecretsMode,
 }
 
 /// Defines the context window configuration for different models.
@@ -57,6 +181,16 @@ pub struct ModelConfig {
     pub max_output_tokens: usize,
     /// Reserved tokens for system prompt and other overhead.
     pub reserved_tokens: usize,
+    /// How many chunk requests may be in flight at the same time.
+    #[serde(default = "default_max_concurrency")]
+    pub max_concurrency: usize,
+    /// Upper bound on requests per minute, unlimited when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+}
+
+fn default_max_concurrency() -> usize {
+    4
 }
 
 /// Defines all LLM providers and their configurations.
@@ -64,6 +198,7 @@ pub struct ModelConfig {
 pub struct LLMProviders {
     pub openai: Option<OpenAIProvider>,
     pub gemini: Option<GeminiProvider>,
+    pub exec: Option<ExecProvider>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -72,7 +207,8 @@ pub struct OpenAIProvider {
     pub api_base: Option<String>,
     pub models: HashMap<String, ModelConfig>,
     pub default_model: String,
-    pub proxy: Option<String>,
+    #[serde(flatten)]
+    pub http: HttpConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -80,7 +216,68 @@ pub struct GeminiProvider {
     pub api_key: String,
     pub models: HashMap<String, ModelConfig>,
     pub default_model: String,
+    #[serde(flatten)]
+    pub http: HttpConfig,
+}
+
+/// An external command that speaks matecode's JSON protocol over stdin/stdout.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExecProvider {
+    /// The program to spawn for every request.
+    pub command: String,
+    /// Arguments passed to the program.
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub models: HashMap<String, ModelConfig>,
+    pub default_model: String,
+    /// Free-form parameters forwarded as `params` in the request.
+    #[serde(default)]
+    pub params: HashMap<String, serde_json::Value>,
+    /// Seconds to wait for the command before killing it, unless `task_timeouts` sets a limit for the task.
+    #[serde(default = "default_exec_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Time limit in seconds per task, used instead of `timeout_secs`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub task_timeouts: HashMap<String, u64>,
+}
+
+fn default_exec_timeout_secs() -> u64 {
+    120
+}
+
+/// HTTP transport settings shared by all providers (proxy, TLS, headers).
+///
+/// The fields are flattened into the provider table, so `proxy = "..."`
+/// keeps working exactly as before.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct HttpConfig {
+    /// Proxy URL used for all requests, e.g. `http://127.0.0.1:7890`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub proxy: Option<String>,
+    /// Comma separated hosts that bypass the proxy, same syntax as `NO_PROXY`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+    /// Extra headers sent with every request (tenant id, trace id, ...).
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub extra_headers: HashMap<String, String>,
+    /// Path to a PEM or DER encoded CA certificate for private gateways.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert_path: Option<String>,
+    /// Skip TLS certificate verification. Only use this for local testing.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub danger_accept_invalid_certs: bool,
+    /// Overrides the default `matecode/<version>` user agent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// Seconds allowed for establishing a connection (default 10).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout_secs: Option<u64>,
+    /// Seconds to wait for data from the server before giving up (default 120).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_timeout_secs: Option<u64>,
+    /// Overall time limit in seconds per task, e.g. `{ summarize = 60, combine = 180 }`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub task_timeouts: HashMap<String, u64>,
 }
 
 /// Creates a default configuration file and directory structure.
@@ -106,6 +303,8 @@ pub async fn create_default_config() -> Result<()> {
                 max_tokens: 16_384, // 大多数私有化模型的常见配置
                 max_output_tokens: 4_096,
                 reserved_tokens: 1_000,
+                max_concurrency: default_max_concurrency(),
+                requests_per_minute: None,
             },
         );
 
@@ -118,6 +317,8 @@ pub async fn create_default_config() -> Result<()> {
                 max_tokens: 1_048_576, // Gemini 2.5 Flash 的实际参数
                 max_output_tokens: 8_192,
                 reserved_tokens: 2_000,
+                max_concurrency: default_max_concurrency(),
+                requests_per_minute: None,
             },
         );
 
@@ -130,15 +331,20 @@ pub async fn create_default_config() -> Result<()> {
                     api_base: Some("http://localhost:8000/v1".to_string()),
                     models: openai_models,
                     default_model: "qwen2.5-72b-instruct".to_string(),
-                    proxy: None,
+                    http: HttpConfig::default(),
                 }),
                 gemini: Some(GeminiProvider {
                     api_key: "YOUR_GEMINI_API_KEY".to_string(),
                     models: gemini_models,
                     default_model: "gemini-2.0-flash-exp".to_string(),
-                    proxy: None,
+                    http: HttpConfig::default(),
                 }),
+                exec: None,
             },
+            secrets: SecretsConfig::default(),
+            git_backend: GitBackendKind::default(),
+            context: ContextConfig::default(),
+            issue: IssueConfig::default(),
         };
 
         let config_content = toml::to_string_pretty(&default_config)?;
@@ -201,6 +407,14 @@ dist/
 .gradle/
 out/
 
+# 锁文件 (在提交信息的上下文中只保留一行摘要)
+Cargo.lock
+package-lock.json
+yarn.lock
+pnpm-lock.yaml
+poetry.lock
+go.sum
+
 # 日志文件
 *.log
 logs/
@@ -310,6 +524,17 @@ fn validate_config(config: &Config) -> Result<()> {
                 ));
             }
         }
+        "exec" => {
+            if let Some(exec) = &config.llm.exec {
+                if exec.command.trim().is_empty() {
+                    return Err(anyhow::anyhow!("请在配置文件中设置外部 LLM 命令 (llm.exec.command)"));
+                }
+            } else {
+                return Err(anyhow::anyhow!(
+                    "选择了 exec 提供商，但未配置 exec 设置"
+                ));
+            }
+        }
         _ => {
             return Err(anyhow::anyhow!("不支持的 LLM 提供商: {}", config.provider));
         }
@@ -317,30 +542,35 @@ fn validate_config(config: &Config) -> Result<()> {
     Ok(())
 }
 
-async fn create_default_prompts(prompts_dir: &Path) -> Result<()> {
-    // 定义所有提示词模板
-    let prompt_templates = vec![
-        ("commit.toml", get_commit_prompt_template()),
-        ("review.toml", get_review_prompt_template()),
-        ("report.toml", get_report_prompt_template()),
-        ("summarize.toml", get_summarize_prompt_template()),
-        ("combine.toml", get_combine_prompt_template()),
-        ("understand.toml", get_understand_prompt_template()),
-        ("plan_clarify.toml", get_plan_clarify_prompt_template()),
+/// 所有内置的提示词模板 (名称, 内容)，名称对应 `prompts/<名称>.toml`
+fn default_prompt_templates() -> Vec<(&'static str, &'static str)> {
+    vec![
+        ("commit", get_commit_prompt_template()),
+        ("review", get_review_prompt_template()),
+        ("report", get_report_prompt_template()),
+        ("summarize", get_summarize_prompt_template()),
+        ("combine", get_combine_prompt_template()),
+        ("pr", get_pr_prompt_template()),
+        ("changelog", get_changelog_prompt_template()),
+        ("release", get_release_prompt_template()),
+        ("understand", get_understand_prompt_template()),
+        ("plan_clarify", get_plan_clarify_prompt_template()),
         (
-            "plan_clarify_specific.toml",
+            "plan_clarify_specific",
             get_plan_clarify_specific_prompt_template(),
         ),
-        ("plan_generate.toml", get_plan_generate_prompt_template()),
-        ("doc_generate.toml", get_doc_generate_prompt_template()),
+        ("plan_generate", get_plan_generate_prompt_template()),
+        ("doc_generate", get_doc_generate_prompt_template()),
         (
-            "diagram_generate.toml",
+            "diagram_generate",
             get_diagram_generate_prompt_template(),
         ),
-    ];
+    ]
+}
 
-    for (filename, content) in prompt_templates {
-        let file_path = prompts_dir.join(filename);
+async fn create_default_prompts(prompts_dir: &Path) -> Result<()> {
+    for (name, content) in default_prompt_templates() {
+        let file_path = prompts_dir.join(format!("{name}.toml"));
 
         // 只在文件不存在时才创建
         if !file_path.exists() {
@@ -356,13 +586,13 @@ async fn create_default_prompts(prompts_dir: &Path) -> Result<()> {
 
 fn get_commit_prompt_template() -> &'static str {
     r#"[system]
-你是一位专业的 Git commit message 编写专家，你的目标是生成人类工程师编写的 commit message。你的回应**只能**包含 commit message 内容，不要有其他任何解释。严格遵守 Angular 规范，但描述部分使用中文。
+你是一位专业的 Git commit message 编写专家，你的目标是生成人类工程师编写的 commit message。你的回应**只能**包含 commit message 内容，不要有其他任何解释。格式遵循本仓库的提交习惯，语言遵循下面的语言要求。
 
 **重要：语言要求**
 {language_instruction}
 
 [user]
-请根据以下的项目上下文和 git diff 内容生成一个中文 git commit message。
+请根据以下的项目上下文和 git diff 内容生成一个 git commit message。
 你需要根据项目的改动信息，来生成一个考虑到对项目的影响，而不是只根据某个文件的改动生成一个简单的commit_message。
 
 <project_context>
@@ -372,19 +602,34 @@ fn get_commit_prompt_template() -> &'static str {
 {affected_files}
 </project_context>
 
+<symbol_changes>
+{symbol_changes}
+</symbol_changes>
+
+<file_history>
+{file_history}
+</file_history>
+
+<style_guide>
+{style_guide}
+</style_guide>
+
 <rules>
 1.  **Header (第一行)**:
-    -   `type` 使用英文 (如 feat, fix, chore)。
-    -   `scope` (可选) 概括变更涉及的模块。
-    -   `subject` (主题) 必须用清晰的中文简明扼要地描述变更内容，不超过50个字符。
+    -   格式 (是否使用 type 和 scope、工单号、gitmoji、标题长度) 按照 <style_guide>；<style_guide> 为 (none) 时使用 Conventional Commits，即 `type(scope): subject`，`type` 使用英文 (如 feat, fix, chore)，`scope` (可选) 概括变更涉及的模块。
+    -   `subject` (主题) 简明扼要地描述变更内容，不超过50个字符。
 2.  **Body (正文, 可选)**:
     -   正文应详细解释 **为什么** 需要这次变更，解决了什么问题。
     -   描述这次变更是 **如何** 实现的，特别是关键的实现思路。
     -   避免使用AI化的、过于正式的语言（例如，不要写 "本次提交新增了..."，而应该更直接地描述）。
-3.  **输出**: 只输出被 <commit_message> 标签包裹的 commit message。
+    -   <symbol_changes> 列出了改动所在的函数、类型以及新增、删除和签名变化的声明，描述时优先引用这些名称。
+    -   <file_history> 列出了这些文件最近的提交标题，用来理解这段代码此前的意图；不要照搬其中的描述。
+3.  **仓库习惯**: <style_guide> 是从本仓库最近的提交中总结的习惯，scope 优先使用其中建议的已有写法；没有工单号时不要编造。标题和正文使用语言要求中的语言，除非 <style_guide> 显示仓库一直使用另一种语言。
+4.  **输出**: 只输出被 <commit_message> 标签包裹的 commit message。
 </rules>
 
 <example_good>
+(示例只展示标题和正文的结构，格式和语言以上面的要求为准)
 <commit_message>
 feat(api): 实现用户认证功能
 
@@ -507,18 +752,22 @@ fn get_summarize_prompt_template() -> &'static str {
 {language_instruction}
 
 [user]
-请分析以下代码变更并生成简洁的中文摘要。
+请分析以下代码变更并生成简洁的摘要。
 
 <context>
 项目文件数: {total_files}
 涉及文件: {chunk_files}
 </context>
 
+<symbol_changes>
+{symbol_changes}
+</symbol_changes>
+
 <diff>
 {diff_content}
 </diff>
 
-请用中文总结这个代码块的主要变更，重点关注功能性改变。
+请总结这个代码块的主要变更，重点关注功能性改变，并提及 <symbol_changes> 中列出的关键函数或类型。
 **注意**：只需要描述变更内容，不要生成完整的commit message格式。
 
 例如:
@@ -530,18 +779,18 @@ fn get_summarize_prompt_template() -> &'static str {
 
 fn get_combine_prompt_template() -> &'static str {
     r#"[system]
-你是一个根据代码变更摘要生成 Conventional Commits 规范的 git commit message 的专家。你的回应应该**只能**包含被 <commit_message> 标签包裹的 commit message，不包含任何额外的解释或引言。
+你是一个根据代码变更摘要生成 git commit message 的专家。你的回应应该**只能**包含被 <commit_message> 标签包裹的 commit message，不包含任何额外的解释或引言。
 
 **重要：语言要求**
 {language_instruction}
  
 [user]
-请根据以下的项目上下文和代码变更摘要，为我生成一个高质量的、人类可读的中文 git commit message。
+请根据以下的项目上下文和代码变更摘要，为我生成一个高质量的、人类可读的 git commit message。
  
 **请注意：**
 *   你的目标是提供一个**高层次的总结**，解释本次系列变更的**核心目的**和**主要实现**，而不是简单地罗列每个文件的具体修改点。
 *   将多个相关的重构或优化操作归纳为一个主要的改动点，并用简洁的语言描述其**整体价值**。
-*   严格遵守 Conventional Commits 规范（例如：`feat:`, `fix:`, `refactor:`, `chore:`, `docs:`, `style:`, `test:`, `perf:`, `build:`, `ci:`, `revert:`）。
+*   标题格式按照 <style_guide> 中的仓库习惯；<style_guide> 为 (none) 时使用 Conventional Commits（例如：`feat:`, `fix:`, `refactor:`, `chore:`, `docs:`, `style:`, `test:`, `perf:`, `build:`, `ci:`, `revert:`）。
 *   commit message 的主体部分应包含对本次变更的**简要描述**，说明为什么要做这些改动以及它们解决了什么问题。
 *   如果可能，使用**动词开头**的简洁表述来概括主要改动。
  
@@ -554,6 +803,23 @@ fn get_combine_prompt_template() -> &'static str {
  
 </project_context>
  
+<symbol_changes>
+ 
+{symbol_changes}
+ 
+</symbol_changes>
+ 
+<file_history>
+ 
+{file_history}
+ 
+</file_history>
+ 
+<style_guide>
+ 
+{style_guide}
+ 
+</style_guide>
  
 <summaries>
  
@@ -564,10 +830,11 @@ fn get_combine_prompt_template() -> &'static str {
 <rules>
  
 1.  **核心目的与主要实现**: 提炼本次系列变更的**核心目的**和**主要实现方式**，用一两句话概括。避免逐条列出文件或函数的修改。
-2.  **Conventional Commits 规范**: 严格遵守 Conventional Commits 规范，包括类型（type）、作用域（scope，如果适用）和描述（subject）。
-3.  **主体内容**: commit message 的主体部分应提供更详细的解释，说明本次变更的背景、原因和带来的好处。
-4.  **语言风格**: 使用简洁、清晰、专业且易于理解的中文。
-5.  **输出格式**: 只输出被 <commit_message> 标签包裹的 commit message。
+2.  **标题格式**: 是否使用类型（type）、作用域（scope）、工单号、gitmoji 以及标题长度按照 <style_guide>；<style_guide> 为 (none) 时使用 Conventional Commits 规范。
+3.  **主体内容**: commit message 的主体部分应提供更详细的解释，说明本次变更的背景、原因和带来的好处。<file_history> 中这些文件最近的提交标题可以帮助理解背景。
+4.  **语言风格**: 简洁、清晰、专业且易于理解，使用语言要求中的语言，除非 <style_guide> 显示仓库一直使用另一种语言。
+5.  **仓库习惯**: <style_guide> 是从本仓库最近的提交中总结的习惯，scope 优先使用其中建议的已有写法；没有工单号时不要编造。
+6.  **输出格式**: 只输出被 <commit_message> 标签包裹的 commit message。
  
 </rules>
  
@@ -587,15 +854,168 @@ feat(history): 引入提交历史归档与日报生成功能
 "#
 }
 
+fn get_pr_prompt_template() -> &'static str {
+    r#"[system]
+你是一位经验丰富的软件工程师，负责为代码评审者撰写清晰的 Pull Request 标题和描述。你的回应**只能**包含被 <pr_title> 和 <pr_body> 标签包裹的内容，不要有其他任何解释。
+
+**重要：语言要求**
+{language_instruction}
+
+[user]
+请根据以下信息，为当前分支相对 `{base}` 的改动撰写一个 Pull Request。
+
+<project_context>
+{project_tree}
+
+本次修改影响的文件 ({total_files} 个):
+{affected_files}
+</project_context>
+
+<commits>
+{commits}
+</commits>
+
+<symbol_changes>
+{symbol_changes}
+</symbol_changes>
+
+<changes>
+{changes}
+</changes>
+
+<pr_template>
+{pr_template}
+</pr_template>
+
+<rules>
+1.  **标题**: 一行，不超过 72 个字符，概括整个分支的目的；如果提交遵循 Conventional Commits，标题也使用相同的 `type(scope): subject` 形式。
+2.  **描述**: 使用 Markdown。
+    -   如果 <pr_template> 中提供了模板，按模板原有的章节标题和顺序逐一填写，删除模板中的 HTML 注释和说明性占位文字，勾选框只勾选能从改动中确认的项。
+    -   如果没有模板，使用以下章节：`## Summary` (为什么需要这些改动)、`## Changes` (按模块列出主要改动)、`## Testing` (如何验证，提到新增或修改的测试)、`## Risks` (兼容性、迁移或需要评审者特别注意的地方)。
+3.  只描述能从提交和改动中确认的内容，不要编造测试结果或关联的 issue。
+4.  **输出**: 标题放在 <pr_title> 标签中，描述放在 <pr_body> 标签中。
+</rules>
+
+<example>
+<pr_title>feat(auth): 支持基于 JWT 的登录</pr_title>
+<pr_body>
+## Summary
+...
+</pr_body>
+</example>
+"#
+}
+
+fn get_changelog_prompt_template() -> &'static str {
+    r#"[system]
+你是一位负责撰写发布说明的技术写作者，擅长把开发者的提交信息改写成面向使用者的变更日志。你的回应**只能**包含被 <changelog> 标签包裹的内容，不要有其他任何解释。
+
+**重要：语言要求**
+{language_instruction}
+
+[user]
+请润色以下 Keep a Changelog 格式的变更日志草稿。
+
+<changelog_draft>
+{changelog}
+</changelog_draft>
+
+<rules>
+1.  **结构**: 原样保留版本标题行 (`## [...]`)、所有 `###` 章节标题及其顺序，不要增加或删除章节。
+2.  **条目**: 每个条目保持一行，以 `- ` 开头，描述改动对使用者的影响而不是实现细节；保留条目开头加粗的 scope 和末尾括号中的提交 sha。
+3.  可以合并描述同一改动的重复条目 (合并时保留所有 sha)，但不要编造草稿中没有的内容。
+4.  **输出**: 润色后的完整段落放在 <changelog> 标签中。
+</rules>
+"#
+}
+
+fn get_release_prompt_template() -> &'static str {
+    r#"[system]
+你是一位负责撰写发布说明的技术写作者，读者是这个项目的使用者，而不是开发者。你的回应**只能**包含被 <release_notes> 标签包裹的内容，不要有其他任何解释。
+
+**重要：语言要求**
+{language_instruction}
+
+[user]
+请为版本 `{tag}` 撰写发布说明，上一个版本是 `{previous_tag}`。
+
+<changelog>
+{changelog}
+</changelog>
+
+<commits>
+{commits}
+</commits>
+
+<rules>
+1.  **开头**: 用一到两句话概括这个版本最重要的变化。
+2.  **正文**: 使用 Markdown，按 `### ⚠ Breaking Changes`、`### Highlights`、`### Fixes`、`### Other Changes` 分组，没有内容的分组直接省略。破坏性变更必须说明使用者需要做什么迁移。
+3.  以 <changelog> 为准，<commits> 只用于补充背景；不要列出提交 sha，不要编造没有出现的功能或关联的 issue。
+4.  **输出**: 发布说明放在 <release_notes> 标签中，不要包含版本标题行。
+</rules>
+"#
+}
+
+/// 后来才加入内置模板的占位符。旧版本 `init` 生成的模板文件中没有它们，
+/// 对应的上下文就不会发送给模型，所以读取时提示用户更新模板
+const ADDED_PLACEHOLDERS: &[(&str, &[&str])] = &[
+    (
+        "commit",
+        &["{symbol_changes}", "{file_history}", "{style_guide}"],
+    ),
+    ("summarize", &["{symbol_changes}"]),
+    (
+        "combine",
+        &["{symbol_changes}", "{file_history}", "{style_guide}"],
+    ),
+    ("pr", &["{symbol_changes}"]),
+];
+
+/// 已经提示过的模板，分块总结时同一个模板会被读取多次
+static WARNED_TEMPLATES: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+fn warn_missing_placeholders(name: &str, prompt_path: &Path, content: &str) {
+    let Some((_, placeholders)) = ADDED_PLACEHOLDERS
+        .iter()
+        .find(|(template, _)| *template == name)
+    else {
+        return;
+    };
+    let missing: Vec<&str> = placeholders
+        .iter()
+        .copied()
+        .filter(|placeholder| !content.contains(placeholder))
+        .collect();
+    if missing.is_empty() {
+        return;
+    }
+    let mut warned = WARNED_TEMPLATES.lock().unwrap_or_else(|e| e.into_inner());
+    if warned.iter().any(|template| template == name) {
+        return;
+    }
+    warned.push(name.to_string());
+    eprintln!(
+        "⚠️  {} 缺少占位符 {}，这些上下文不会发送给模型。请参考内置模板补上，或删除该文件以使用内置模板。",
+        prompt_path.display(),
+        missing.join("、")
+    );
+}
+
 pub async fn get_prompt_template(name: &str) -> Result<String> {
     let config_dir = get_config_dir().await?;
     let prompt_path = config_dir.join("prompts").join(format!("{name}.toml"));
 
-    // 如果文件不存在或无法读取，将在下方为指定模板提供内置回退
+    // 文件不存在时使用内置模板，这样升级后新增的模板无需重新运行 init
     let mut content = if prompt_path.exists() {
-        fs::read_to_string(&prompt_path).await?
+        let content = fs::read_to_string(&prompt_path).await?;
+        warn_missing_placeholders(name, &prompt_path, &content);
+        content
     } else {
-        String::new()
+        default_prompt_templates()
+            .into_iter()
+            .find(|(builtin, _)| *builtin == name)
+            .map(|(_, template)| template.to_string())
+            .unwrap_or_default()
     };
 
     // 加载配置以获取语言设置
@@ -0,0 +1,235 @@
+//! src/diff.rs
+//!
+//! 把 `git diff --staged -M --numstat --patch` 的输出解析成按文件组织的模型，
+//! 并为 LLM 渲染紧凑的文本：重命名、二进制、权限和子模块变化只用一行描述。
+
+/// 文件的变化类型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Deleted,
+    Modified,
+    Renamed { from: String, similarity: u8 },
+}
+
+/// 单个文件的变化
+#[derive(Debug, Clone)]
+pub struct FileChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    /// 权限变化 (旧, 新)，例如 100644 -> 100755
+    pub mode_change: Option<(String, String)>,
+    pub binary: bool,
+    pub submodule: bool,
+    /// 每个 hunk 以 `@@` 行开头，包含换行符
+    pub hunks: Vec<String>,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+impl FileChange {
+    fn new(path: String) -> Self {
+        Self {
+            path,
+            kind: ChangeKind::Modified,
+            mode_change: None,
+            binary: false,
+            submodule: false,
+            hunks: Vec::new(),
+            additions: 0,
+            deletions: 0,
+        }
+    }
+
+    pub fn old_path(&self) -> &str {
+        match &self.kind {
+            ChangeKind::Renamed { from, .. } => from,
+            _ => &self.path,
+        }
+    }
+
+    /// 一行描述，例如 "renamed from src/a.rs (95% similar), +2/-1"
+    pub fn summary(&self) -> String {
+        let mut parts = vec![match &self.kind {
+            ChangeKind::Added => "new file".to_string(),
+            ChangeKind::Deleted => "deleted".to_string(),
+            ChangeKind::Modified => "modified".to_string(),
+            ChangeKind::Renamed { from, similarity } => {
+                format!("renamed from {from} ({similarity}% similar)")
+            }
+        }];
+
+        if let Some((old, new)) = &self.mode_change {
+            parts.push(format!("mode {old} -> {new}"));
+        }
+        if self.submodule {
+            parts.push(match self.submodule_commits() {
+                (Some(old), Some(new)) => format!("submodule {old} -> {new}"),
+                (None, Some(new)) => format!("submodule at {new}"),
+                (Some(old), None) => format!("submodule was at {old}"),
+                (None, None) => "submodule".to_string(),
+            });
+        } else if self.binary {
+            parts.push("binary".to_string());
+        } else if !self.hunks.is_empty() {
+            parts.push(format!("+{}/-{}", self.additions, self.deletions));
+        } else if matches!(self.kind, ChangeKind::Renamed { .. }) {
+            parts.push("content unchanged".to_string());
+        }
+
+        parts.join(", ")
+    }
+
+    /// `diff --git` 行加一行描述，作为该文件在 prompt 中的文件头
+    pub fn prompt_header(&self) -> String {
+        format!(
+            "diff --git a/{} b/{}\n{}\n",
+            self.old_path(),
+            self.path,
+            self.summary()
+        )
+    }
+
+    /// 该文件中应该原样发送的 hunk；二进制和子模块只需要文件头里的描述
+    pub fn prompt_hunks(&self) -> &[String] {
+        if self.binary || self.submodule {
+            &[]
+        } else {
+            &self.hunks
+        }
+    }
+
+    fn submodule_commits(&self) -> (Option<String>, Option<String>) {
+        let mut old = None;
+        let mut new = None;
+        for line in self.hunks.iter().flat_map(|hunk| hunk.lines()) {
+            if let Some(sha) = line.strip_prefix("-Subproject commit ") {
+                old = Some(sha.chars().take(7).collect());
+            } else if let Some(sha) = line.strip_prefix("+Subproject commit ") {
+                new = Some(sha.chars().take(7).collect());
+            }
+        }
+        (old, new)
+    }
+}
+
+/// numstat 中的一行：二进制文件的增删行数为 `-`
+struct NumStat {
+    additions: Option<usize>,
+    deletions: Option<usize>,
+}
+
+fn parse_numstat_line(line: &str) -> Option<NumStat> {
+    let mut fields = line.splitn(3, '\t');
+    let additions = fields.next()?;
+    let deletions = fields.next()?;
+    fields.next()?;
+    let parse = |field: &str| -> Option<Option<usize>> {
+        if field == "-" {
+            Some(None)
+        } else {
+            field.parse().ok().map(Some)
+        }
+    };
+    Some(NumStat {
+        additions: parse(additions)?,
+        deletions: parse(deletions)?,
+    })
+}
+
+/// 解析 diff 输出。开头可以带有 `--numstat` 的统计行，它们与后面的文件顺序一致。
+pub fn parse_diff(output: &str) -> Vec<FileChange> {
+    let mut numstats: Vec<NumStat> = Vec::new();
+    let mut files: Vec<FileChange> = Vec::new();
+    let mut similarity: u8 = 100;
+
+    for line in output.split_inclusive('\n') {
+        let trimmed = line.trim_end_matches(['\n', '\r']);
+
+        if let Some(rest) = trimmed.strip_prefix("diff --git ") {
+            files.push(FileChange::new(path_from_diff_git_line(rest)));
+            similarity = 100;
+            continue;
+        }
+
+        let Some(file) = files.last_mut() else {
+            if let Some(stat) = parse_numstat_line(trimmed) {
+                numstats.push(stat);
+            }
+            continue;
+        };
+
+        if line.starts_with("@@") {
+            file.hunks.push(line.to_string());
+            continue;
+        }
+        if let Some(hunk) = file.hunks.last_mut() {
+            hunk.push_str(line);
+            continue;
+        }
+
+        // 扩展头部
+        if let Some(mode) = trimmed.strip_prefix("new file mode ") {
+            file.kind = ChangeKind::Added;
+            file.submodule |= mode == "160000";
+        } else if let Some(mode) = trimmed.strip_prefix("deleted file mode ") {
+            file.kind = ChangeKind::Deleted;
+            file.submodule |= mode == "160000";
+        } else if let Some(mode) = trimmed.strip_prefix("old mode ") {
+            file.mode_change = Some((mode.to_string(), String::new()));
+        } else if let Some(mode) = trimmed.strip_prefix("new mode ") {
+            if let Some((_, new)) = file.mode_change.as_mut() {
+                *new = mode.to_string();
+            }
+        } else if let Some(value) = trimmed.strip_prefix("similarity index ") {
+            similarity = value.trim_end_matches('%').parse().unwrap_or(100);
+        } else if let Some(from) = trimmed.strip_prefix("rename from ") {
+            file.kind = ChangeKind::Renamed {
+                from: from.to_string(),
+                similarity,
+            };
+        } else if let Some(to) = trimmed.strip_prefix("rename to ") {
+            file.path = to.to_string();
+        } else if let Some(index) = trimmed.strip_prefix("index ") {
+            file.submodule |= index.ends_with(" 160000");
+        } else if trimmed.starts_with("Binary files ") || trimmed == "GIT binary patch" {
+            file.binary = true;
+        } else if let Some(path) = trimmed.strip_prefix("+++ b/") {
+            file.path = path.to_string();
+        }
+    }
+
+    let numstats_match = numstats.len() == files.len();
+    for (index, file) in files.iter_mut().enumerate() {
+        match numstats_match.then(|| &numstats[index]) {
+            Some(NumStat {
+                additions: Some(additions),
+                deletions: Some(deletions),
+            }) => {
+                file.additions = *additions;
+                file.deletions = *deletions;
+            }
+            Some(_) => file.binary = true,
+            None => count_hunk_lines(file),
+        }
+    }
+
+    files
+}
+
+fn count_hunk_lines(file: &mut FileChange) {
+    for line in file.hunks.iter().flat_map(|hunk| hunk.lines().skip(1)) {
+        if line.starts_with('+') {
+            file.additions += 1;
+        } else if line.starts_with('-') {
+            file.deletions += 1;
+        }
+    }
+}
+
+/// `a/<old> b/<new>`，路径中可能包含空格，所以从最后一个 ` b/` 处切分
+fn path_from_diff_git_line(rest: &str) -> String {
+    rest.rsplit_once(" b/")
+        .map(|(_, path)| path.to_string())
+        .unwrap_or_default()
+}
@@ -1,16 +1,79 @@
+use crate::compact;
 use crate::config;
+use crate::diff;
+use crate::git_backend::{self, CommitInfo, DiffOptions, DiffScope};
+use crate::ignore_rules::{self, IgnoreRules};
+use crate::project_tree;
+use crate::style::{self, StyleProfile};
+use crate::symbols::{self, FileSymbols};
 use anyhow::{Context, Result, anyhow};
+use futures::stream::{self, StreamExt};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 
+/// 提交记录在 prompt 中最多占用的字符数
+const MAX_COMMITS_CHARS: usize = 8_000;
+/// 最多为这么多个文件查询提交历史，避免大改动时启动过多 git 进程
+const MAX_HISTORY_FILES: usize = 30;
+/// 同时查询文件历史的数量
+const HISTORY_CONCURRENCY: usize = 8;
+
 #[derive(Debug, Clone)]
 pub struct ProjectContext {
     pub project_tree: String,
     pub total_files: usize,
     pub affected_files: Vec<String>,
+    /// 各文件的符号级变化，不支持的语言和被忽略的文件没有条目
+    pub symbols: Vec<FileSymbols>,
+    /// 各文件最近几次提交的标题，从新到旧；没有开启或没有历史的文件没有条目
+    pub history: BTreeMap<String, Vec<String>>,
+    /// 从最近的提交中总结的提交习惯，提交太少时为 `None`
+    pub style: Option<StyleProfile>,
 }
 
-impl ProjectContext {}
+impl ProjectContext {
+    /// `files` 中各文件的符号变化，用于 prompt 中的 `{symbol_changes}`
+    pub fn symbol_changes(&self, files: &[String]) -> String {
+        symbols::render_symbol_changes(&self.symbols, files)
+    }
+
+    /// `files` 中各文件最近的提交标题，用于 prompt 中的 `{file_history}`
+    pub fn file_history(&self, files: &[String]) -> String {
+        let mut lines = Vec::new();
+        for file in files {
+            if let Some(subjects) = self.history.get(file) {
+                lines.push(format!("{file}:"));
+                lines.extend(subjects.iter().map(|subject| format!("  - {subject}")));
+            }
+        }
+        if lines.is_empty() {
+            "(none)".to_string()
+        } else {
+            lines.join("\n")
+        }
+    }
+
+    /// 仓库的提交习惯以及 `files` 适合的 scope，用于 prompt 中的 `{style_guide}`
+    pub fn style_guide(&self, files: &[String]) -> String {
+        match &self.style {
+            Some(profile) => profile.render(files),
+            None => "(none)".to_string(),
+        }
+    }
+
+    /// commit 和 combine 提示词中这些上下文占用的 token 数量，留给 diff 和摘要的预算要扣掉它
+    pub fn prompt_tokens(&self) -> usize {
+        let files = &self.affected_files;
+        estimeate_token_count(&self.project_tree)
+            + estimeate_token_count(&files.join(", "))
+            + estimeate_token_count(&self.symbol_changes(files))
+            + estimeate_token_count(&self.file_history(files))
+            + estimeate_token_count(&self.style_guide(files))
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct DiffChunk {
@@ -40,12 +103,42 @@ pub async fn run_git_command(args: &[&str]) -> Result<String> {
         .output()
         .await
         .context("执行Git command 失败")?;
+    git_command_output(output)
+}
+
+/// 运行git命令，并把 `input` 写入它的 stdin，例如 `git patch-id`
+pub async fn run_git_command_with_input(args: &[&str], input: &str) -> Result<String> {
+    let mut child = Command::new("git")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("执行Git command 失败")?;
+
+    let mut stdin = child
+        .stdin
+        .take()
+        .context("无法获取 Git command 的 stdin")?;
+    stdin
+        .write_all(input.as_bytes())
+        .await
+        .context("向 Git command 写入输入失败")?;
+    drop(stdin);
+
+    let output = child
+        .wait_with_output()
+        .await
+        .context("执行Git command 失败")?;
+    git_command_output(output)
+}
 
+fn git_command_output(output: std::process::Output) -> Result<String> {
     if output.status.success() {
-        Ok(String::from_utf8(output.stdout).context("git命令解析成功")?)
+        // 文件名或提交信息可能不是合法的 UTF-8，不能因此让整个命令失败
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
     } else {
-        let stderr = String::from_utf8(output.stderr)
-            .unwrap_or_else(|_| "Could not read stderr".to_string());
+        let stderr = String::from_utf8_lossy(&output.stderr);
 
         Err(anyhow!(
             "Git command 执行失败, status: {}\n{}",
@@ -55,27 +148,97 @@ pub async fn run_git_command(args: &[&str]) -> Result<String> {
     }
 }
 
-/// 获取暂存区的diff信息
-pub async fn get_staged_diff() -> Result<String> {
-    run_git_command(&["diff", "--staged"]).await
+/// 获取 `scope` 范围内的diff信息 (暂存区、工作区或提交区间)
+/// 带有 numstat 统计并开启重命名检测，由 [`diff::parse_diff`] 解析。
+/// 上下文行数来自 `context`；开启 `function_context` 后，只有包含完整函数的 diff
+/// (按发送给 LLM 的形式计算) 仍然放得下时才使用它
+pub async fn get_diff(
+    scope: &DiffScope,
+    model_config: &config::ModelConfig,
+    context: &config::ContextConfig,
+) -> Result<String> {
+    let backend = git_backend::backend().await?;
+    let options = DiffOptions {
+        context_lines: context.lines,
+        function_context: false,
+    };
+
+    if context.function_context {
+        let function_options = DiffOptions {
+            function_context: true,
+            ..options
+        };
+        let diff = backend.diff(scope, &function_options).await?;
+        // 按发送给 LLM 的形式估算：被忽略的文件、锁文件和生成的文件只剩一行摘要，不占预算
+        let rules = IgnoreRules::load().await?;
+        let files = compact::strip_noise(&prompt_file_diffs(&diff, &rules));
+        let prompt_diff = join_file_diffs(&diff, &files);
+        if estimeate_token_count(&prompt_diff) <= function_context_budget(model_config) {
+            return Ok(diff);
+        }
+    }
+    backend.diff(scope, &options).await
 }
 
-/// 获取git项目名称
-pub async fn get_git_repo_name() -> Result<String> {
-    let output = run_git_command(&["rev-parse", "--show-toplevel"]).await?;
+/// 包含完整函数的 diff 要能一次发送，还要给目录树等上下文留出空间
+fn function_context_budget(model_config: &config::ModelConfig) -> usize {
+    model_config
+        .max_tokens
+        .saturating_sub(model_config.reserved_tokens)
+        * 3
+        / 4
+}
 
-    let path = std::path::Path::new(output.trim());
+/// 每个文件最近 `limit` 条提交的标题，新增的文件没有历史
+async fn get_file_history(files: &[String], limit: usize) -> Result<BTreeMap<String, Vec<String>>> {
+    let mut history = BTreeMap::new();
+    if limit == 0 {
+        return Ok(history);
+    }
+    let backend = git_backend::backend().await?;
+    // 每个文件一次查询，多个文件同时进行
+    let mut results = stream::iter(files.iter().take(MAX_HISTORY_FILES))
+        .map(|file| {
+            let backend = &backend;
+            async move {
+                let commits = backend.file_history(file, limit).await?;
+                Ok::<_, anyhow::Error>((file, commits))
+            }
+        })
+        .buffered(HISTORY_CONCURRENCY);
+    while let Some(result) = results.next().await {
+        let (file, commits) = result?;
+        let subjects: Vec<String> = commits
+            .iter()
+            .map(|commit| commit.subject().to_string())
+            .collect();
+        if !subjects.is_empty() {
+            history.insert(file.clone(), subjects);
+        }
+    }
+    Ok(history)
+}
 
-    Ok(path
-        .file_name()
-        .and_then(|s| s.to_str())
-        .unwrap_or("unknown_project")
-        .to_string())
+/// 获取仓库根目录
+pub async fn get_repo_root() -> Result<PathBuf> {
+    Ok(git_backend::repo_context().await?.toplevel.clone())
 }
 
-/// 获取最近一条commit-message
-pub async fn get_last_commit_message() -> Result<String> {
-    run_git_command(&["log", "-1", "--pretty=%B"]).await
+/// 从旧到新列出提交，附带正文，供 prompt 使用
+pub fn format_commits(commits: &[CommitInfo]) -> String {
+    let mut text = String::new();
+    for (index, commit) in commits.iter().rev().enumerate() {
+        let mut entry = format!("- {} {}\n", commit.short_sha(), commit.subject());
+        for line in commit.message.lines().skip(1).filter(|line| !line.trim().is_empty()) {
+            entry.push_str(&format!("  {line}\n"));
+        }
+        if text.len() + entry.len() > MAX_COMMITS_CHARS {
+            text.push_str(&format!("... 还有 {} 个提交\n", commits.len() - index));
+            break;
+        }
+        text.push_str(&entry);
+    }
+    text
 }
 
 /// 判断当前目录是否是一个git仓库
@@ -88,23 +251,26 @@ pub async fn check_is_git_repo() -> bool {
 
 /// 获取暂存区文件列表
 pub async fn get_staged_files() -> Result<Vec<String>> {
-    let output = run_git_command(&["diff", "--name-only", "--cached"]).await?;
-    Ok(output.lines().map(String::from).collect())
+    git_backend::backend().await?.staged_files().await
 }
 
-/// 获取项目上下文信息
-pub async fn get_project_context() -> Result<ProjectContext> {
-    let affected_files_str = run_git_command(&["diff", "--staged", "--name-only"]).await?;
-    let affected_files = affected_files_str.lines().map(String::from).collect();
-
-    let project_tree = "File tree generation is disabled for performance.".to_string();
-
-    let total_files = 0;
+/// 获取项目上下文信息，目录树的大小不超过 `tree_token_budget`
+pub async fn get_project_context(
+    affected_files: Vec<String>,
+    tree_token_budget: usize,
+) -> Result<ProjectContext> {
+    let (project_tree, total_files) =
+        project_tree::build_project_tree(&affected_files, tree_token_budget)
+            .await
+            .unwrap_or_else(|_| ("无法生成项目结构。".to_string(), 0));
 
     Ok(ProjectContext {
         project_tree,
         total_files,
         affected_files,
+        symbols: Vec::new(),
+        history: BTreeMap::new(),
+        style: None,
     })
 }
 
@@ -136,36 +302,263 @@ pub fn chunk_large_text(text: &str, token_limit: usize) -> Vec<String> {
     chunks
 }
 
-/// diff内容分析，主要分析内容长度,进行合适的分割处理
-pub async fn analyze_diff(diff: &str, model_config: &config::ModelConfig) -> Result<DiffAnalysis> {
-    // 项目上下文
-    let project_context = get_project_context().await?;
+/// 单个文件在 prompt 中的内容：文件头（`diff --git` 行和一行描述）和各个 hunk
+#[derive(Debug, Clone)]
+pub struct FileDiff {
+    pub path: String,
+    pub header: String,
+    pub hunks: Vec<String>,
+}
+
+impl FileDiff {
+    fn content(&self) -> String {
+        let mut content = self.header.clone();
+        for hunk in &self.hunks {
+            content.push_str(hunk);
+        }
+        content
+    }
+}
 
+/// 解析 diff 并转换成发送给 LLM 的形式。
+/// 命中 `.matecode-ignore` 的文件只保留 `diff --git` 行和一行摘要，这样切分时它们仍然是独立的文件。
+pub fn prompt_file_diffs(diff: &str, rules: &IgnoreRules) -> Vec<FileDiff> {
+    diff::parse_diff(diff)
+        .into_iter()
+        .map(|change| {
+            if rules.is_ignored(Path::new(&change.path), false) {
+                let stub = ignore_rules::ignored_file_stub(
+                    &change.path,
+                    change.additions,
+                    change.deletions,
+                );
+                FileDiff {
+                    header: format!(
+                        "diff --git a/{} b/{}\n[matecode-ignore] {}\n",
+                        change.old_path(),
+                        change.path,
+                        stub
+                    ),
+                    path: change.path,
+                    hunks: Vec::new(),
+                }
+            } else {
+                FileDiff {
+                    header: change.prompt_header(),
+                    hunks: change.prompt_hunks().to_vec(),
+                    path: change.path,
+                }
+            }
+        })
+        .collect()
+}
+
+/// 把暂存的 diff 渲染成 prompt 中使用的紧凑文本；无法解析时原样返回
+pub async fn render_diff_for_prompt(diff: &str) -> Result<String> {
+    let rules = IgnoreRules::load().await?;
+    Ok(join_file_diffs(diff, &prompt_file_diffs(diff, &rules)))
+}
+
+fn join_file_diffs(raw_diff: &str, files: &[FileDiff]) -> String {
+    if files.is_empty() {
+        raw_diff.to_string()
+    } else {
+        files.iter().map(FileDiff::content).collect()
+    }
+}
+
+/// 沿文件和 hunk 边界切分 diff：小文件打包到同一块，
+/// 超出预算的文件按 hunk 切分，并在每一块中重复文件头。
+pub fn chunk_diff_by_files(files: &[FileDiff], token_limit: usize) -> Vec<DiffChunk> {
+    let mut builder = ChunkBuilder::new(token_limit);
+
+    for file in files {
+        let content = file.content();
+        if estimeate_token_count(&content) <= token_limit {
+            builder.add(&file.path, &content);
+            continue;
+        }
+
+        // 文件本身超出预算：单独成块，按 hunk 切分
+        builder.flush();
+        for hunk in &file.hunks {
+            let piece_tokens = estimeate_token_count(&file.header) + estimeate_token_count(hunk);
+            if piece_tokens <= token_limit {
+                builder.add_hunk(&file.path, &file.header, hunk);
+                continue;
+            }
+
+            // 单个 hunk 仍然过大，只能按行切分，每块都带上文件头和 hunk 头
+            builder.flush();
+            let (hunk_header, hunk_body) = hunk.split_once('\n').unwrap_or((hunk, ""));
+            let prefix = format!("{}{}\n", file.header, hunk_header);
+            let line_limit = token_limit
+                .saturating_sub(estimeate_token_count(&prefix))
+                .max(1);
+            for piece in chunk_large_text(hunk_body, line_limit) {
+                builder.add(&file.path, &format!("{prefix}{piece}"));
+                builder.flush();
+            }
+        }
+        builder.flush();
+    }
+
+    builder.finish()
+}
+
+struct ChunkBuilder {
+    token_limit: usize,
+    chunks: Vec<DiffChunk>,
+    files: Vec<String>,
+    content: String,
+    tokens: usize,
+    /// 当前块中最后一个写入了文件头的文件
+    open_file: Option<String>,
+}
+
+impl ChunkBuilder {
+    fn new(token_limit: usize) -> Self {
+        Self {
+            token_limit,
+            chunks: Vec::new(),
+            files: Vec::new(),
+            content: String::new(),
+            tokens: 0,
+            open_file: None,
+        }
+    }
+
+    fn add(&mut self, path: &str, content: &str) {
+        let tokens = estimeate_token_count(content);
+        if self.tokens + tokens > self.token_limit {
+            self.flush();
+        }
+        self.push(path, content, tokens);
+        self.open_file = None;
+    }
+
+    /// 追加一个 hunk；如果这是该文件在当前块中的第一个 hunk，会先写入文件头
+    fn add_hunk(&mut self, path: &str, header: &str, hunk: &str) {
+        let hunk_tokens = estimeate_token_count(hunk);
+        let continues_file = self.open_file.as_deref() == Some(path);
+        let needed = if continues_file {
+            hunk_tokens
+        } else {
+            hunk_tokens + estimeate_token_count(header)
+        };
+
+        if self.tokens + needed > self.token_limit {
+            self.flush();
+        }
+
+        if self.open_file.as_deref() != Some(path) {
+            self.push(path, header, estimeate_token_count(header));
+            self.open_file = Some(path.to_string());
+        }
+        self.push(path, hunk, hunk_tokens);
+    }
+
+    fn push(&mut self, path: &str, content: &str, tokens: usize) {
+        if !self.files.iter().any(|f| f == path) {
+            self.files.push(path.to_string());
+        }
+        self.content.push_str(content);
+        self.tokens += tokens;
+    }
+
+    fn flush(&mut self) {
+        if !self.content.is_empty() {
+            self.chunks.push(DiffChunk::new(
+                std::mem::take(&mut self.files),
+                std::mem::take(&mut self.content),
+            ));
+        }
+        self.files.clear();
+        self.tokens = 0;
+        self.open_file = None;
+    }
+
+    fn finish(mut self) -> Vec<DiffChunk> {
+        self.flush();
+        self.chunks
+    }
+}
+
+/// diff内容分析，主要分析内容长度,进行合适的分割处理
+pub async fn analyze_diff(
+    diff: &str,
+    model_config: &config::ModelConfig,
+    context: &config::ContextConfig,
+) -> Result<DiffAnalysis> {
     // 剩余可用tokens
-    let available_tokens = model_config.max_tokens - model_config.reserved_tokens;
+    let available_tokens = model_config
+        .max_tokens
+        .saturating_sub(model_config.reserved_tokens)
+        .max(1);
+
+    // 解析成按文件组织的紧凑形式，被忽略的文件只保留一行摘要
+    let rules = IgnoreRules::load().await?;
+    let files = prompt_file_diffs(diff, &rules);
+
+    // 项目上下文，目录树最多占用 1/8 的预算。
+    // 受影响的文件取自 diff 本身，这样工作区和提交区间的改动也能正确标记
+    let affected_files = files.iter().map(|file| file.path.clone()).collect();
+    let mut project_context =
+        get_project_context(affected_files, (available_tokens / 8).min(2_000)).await?;
+    project_context.symbols = files
+        .iter()
+        .filter_map(|file| symbols::extract_file_symbols(&file.path, &file.hunks))
+        .collect();
+
+    project_context.history =
+        get_file_history(&project_context.affected_files, context.file_history).await?;
+    // 提交习惯只是参考，还没有提交或分析失败时不影响生成
+    project_context.style = style::load_profile().await.unwrap_or_default();
+
+    let available_tokens = available_tokens
+        .saturating_sub(project_context.prompt_tokens())
+        .max(1);
+    let mut prompt_diff = join_file_diffs(diff, &files);
 
     // 估算的token，以后可以使用标准的分词器进行计算
-    let total_tokens = estimeate_token_count(diff);
+    let mut total_tokens = estimeate_token_count(&prompt_diff);
+
+    // 放不下时先在本地压缩，压缩后仍然放不下才切分
+    let mut chunk_files = files;
+    if total_tokens > available_tokens {
+        match compact::compact(&chunk_files, available_tokens) {
+            Some(compacted) => {
+                prompt_diff = join_file_diffs(diff, &compacted);
+                total_tokens = estimeate_token_count(&prompt_diff);
+            }
+            None => chunk_files = compact::strip_noise(&chunk_files),
+        }
+    }
+    let diff = prompt_diff.as_str();
 
     // 可以直接使用一个提交处理
     if total_tokens <= available_tokens {
-        return Ok(DiffAnalysis {
+        Ok(DiffAnalysis {
             context: project_context.clone(),
             chunks: vec![DiffChunk::new(
                 project_context.affected_files.clone(),
                 diff.to_string(),
             )],
             needs_chunking: false,
-        });
+        })
     } else {
         let chunking_token_limit = (available_tokens * 3) / 4;
-        let chunks = chunk_large_text(diff, chunking_token_limit);
-        let diff_chunks = chunks
-            .into_iter()
-            .map(|chunk_content| {
-                DiffChunk::new(project_context.affected_files.clone(), chunk_content)
-            })
-            .collect();
+        let mut diff_chunks = chunk_diff_by_files(&chunk_files, chunking_token_limit);
+
+        // 不是标准的 git diff 格式时，退回按行切分
+        if diff_chunks.is_empty() {
+            diff_chunks = chunk_large_text(diff, chunking_token_limit)
+                .into_iter()
+                .map(|chunk_content| {
+                    DiffChunk::new(project_context.affected_files.clone(), chunk_content)
+                })
+                .collect();
+        }
 
         Ok(DiffAnalysis {
             context: project_context,
@@ -174,16 +567,3 @@ pub async fn analyze_diff(diff: &str, model_config: &config::ModelConfig) -> Res
         })
     }
 }
-
-/// Format diff content in the user specified style
-pub fn format_diff_content(file_path: &str, content: &str) -> String {
-    let mut formatted = String::new();
-    formatted.push_str(&format!("Original file: {}\n```\n", file_path));
-    
-    for (line_num, line) in content.lines().enumerate() {
-        formatted.push_str(&format!("{} | {}\n", line_num + 1, line));
-    }
-    
-    formatted.push_str("```\n\n");
-    formatted
-}
@@ -0,0 +1,643 @@
+//! src/git_backend/in_process.rs
+//!
+//! 基于 gix 的进程内实现：不需要启动 `git`，也不要求输出是合法的 UTF-8。
+//! gix 的操作都是同步的，放在 `spawn_blocking` 中执行。
+
+use super::{
+    BranchCommit, CommitInfo, DiffOptions, DiffScope, DiffSource, FileStat, GitBackend, LogFilter,
+    ProcessBackend,
+};
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use gix::bstr::ByteSlice;
+use gix::diff::blob::intern::InternedInput;
+use gix::diff::blob::unified_diff::{ConsumeBinaryHunk, ContextSize};
+use gix::diff::blob::{Algorithm, UnifiedDiff};
+use gix::diff::index::{Action, ChangeRef};
+use gix::index::entry::Mode;
+use gix::status::tree_index::TrackRenames;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// git 判断二进制文件时检查的字节数
+const BINARY_SNIFF_LEN: usize = 8000;
+
+pub struct GixBackend {
+    repo: gix::ThreadSafeRepository,
+    /// gix 还不支持的操作交给 `git` 进程，在同一个目录中运行
+    process: ProcessBackend,
+}
+
+impl GixBackend {
+    pub fn discover(dir: impl AsRef<Path>) -> Result<Self> {
+        let repo = gix::discover(dir.as_ref())
+            .with_context(|| format!("无法打开 git 仓库: {}", dir.as_ref().display()))?;
+        Ok(Self {
+            repo: repo.into_sync(),
+            process: ProcessBackend::new(dir.as_ref()),
+        })
+    }
+
+    async fn with_repo<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(gix::Repository) -> Result<T> + Send + 'static,
+    {
+        let repo = self.repo.clone();
+        tokio::task::spawn_blocking(move || f(repo.to_thread_local())).await?
+    }
+}
+
+#[async_trait]
+impl GitBackend for GixBackend {
+    async fn toplevel(&self) -> Result<PathBuf> {
+        self.with_repo(|repo| {
+            repo.workdir()
+                .map(Path::to_path_buf)
+                .ok_or_else(|| anyhow!("裸仓库没有工作区"))
+        })
+        .await
+    }
+
+    async fn git_dir(&self) -> Result<PathBuf> {
+        self.with_repo(|repo| Ok(repo.git_dir().to_path_buf())).await
+    }
+
+    async fn common_dir(&self) -> Result<PathBuf> {
+        self.with_repo(|repo| Ok(repo.common_dir().to_path_buf())).await
+    }
+
+    async fn diff(&self, scope: &DiffScope, options: &DiffOptions) -> Result<String> {
+        // 进程内只实现最常用的整个暂存区，其他范围、pathspec 和函数上下文交给 git 进程处理
+        if scope.source != DiffSource::Staged
+            || !scope.pathspec.is_empty()
+            || options.function_context
+        {
+            return self.process.diff(scope, options).await;
+        }
+        let context_lines = options.context_lines;
+        self.with_repo(move |repo| staged_diff(&repo, context_lines))
+            .await
+    }
+
+    async fn staged_files(&self) -> Result<Vec<String>> {
+        self.with_repo(|repo| {
+            let mut files = Vec::new();
+            for change in staged_changes(&repo)? {
+                match change {
+                    ChangeRef::Rewrite { location, .. }
+                    | ChangeRef::Addition { location, .. }
+                    | ChangeRef::Deletion { location, .. }
+                    | ChangeRef::Modification { location, .. } => {
+                        files.push(location.to_str_lossy().into_owned())
+                    }
+                }
+            }
+            Ok(files)
+        })
+        .await
+    }
+
+    async fn tracked_files(&self) -> Result<Vec<String>> {
+        self.with_repo(|repo| {
+            let index = repo.index_or_empty()?;
+            Ok(index
+                .entries()
+                .iter()
+                .map(|entry| entry.path(&index).to_str_lossy().into_owned())
+                .collect())
+        })
+        .await
+    }
+
+    async fn log(&self, limit: usize) -> Result<Vec<CommitInfo>> {
+        self.with_repo(move |repo| {
+            // 空仓库没有 HEAD，视为没有提交
+            let Ok(head) = repo.head_id() else {
+                return Ok(Vec::new());
+            };
+            let mut commits = Vec::new();
+            for info in head.ancestors().all()?.take(limit) {
+                commits.push(commit_info(&repo, info?.id)?);
+            }
+            Ok(commits)
+        })
+        .await
+    }
+
+    async fn file_history(&self, path: &str, limit: usize) -> Result<Vec<CommitInfo>> {
+        let path = path.to_string();
+        self.with_repo(move |repo| {
+            // 空仓库没有 HEAD，视为没有提交
+            let Ok(head) = repo.head_id() else {
+                return Ok(Vec::new());
+            };
+            let entry_id = |id: gix::ObjectId| -> Result<Option<gix::ObjectId>> {
+                let tree = repo.find_commit(id)?.tree()?;
+                Ok(tree.lookup_entry_by_path(&path)?.map(|entry| entry.object_id()))
+            };
+
+            let mut commits = Vec::new();
+            let walk = head
+                .ancestors()
+                .sorting(gix::revision::walk::Sorting::ByCommitTime(Default::default()))
+                .all()?;
+            for info in walk {
+                if commits.len() >= limit {
+                    break;
+                }
+                let info = info?;
+                let current = entry_id(info.id)?;
+                let parents: Vec<_> = info.parent_ids().map(|id| id.detach()).collect();
+                // 与 `git log -- <path>` 的历史简化一致：和任一父提交相同就不算修改
+                let mut unchanged = false;
+                for parent in &parents {
+                    if entry_id(*parent)? == current {
+                        unchanged = true;
+                        break;
+                    }
+                }
+                if parents.is_empty() {
+                    unchanged = current.is_none();
+                }
+                if !unchanged {
+                    commits.push(commit_info(&repo, info.id)?);
+                }
+            }
+            Ok(commits)
+        })
+        .await
+    }
+
+    async fn commits_between(&self, base: Option<&str>, head: &str) -> Result<Vec<CommitInfo>> {
+        let base = base.map(String::from);
+        let head = head.to_string();
+        self.with_repo(move |repo| {
+            let hidden = match base {
+                Some(base) => vec![resolve_commit(&repo, &base)?],
+                None => Vec::new(),
+            };
+            let head = resolve_commit(&repo, &head)?;
+            let mut commits = Vec::new();
+            for info in repo.rev_walk([head]).with_hidden(hidden).all()? {
+                commits.push(commit_info(&repo, info?.id)?);
+            }
+            // 与 `git log` 的默认顺序一致：按提交时间从新到旧
+            commits.sort_by_key(|commit| std::cmp::Reverse(commit.timestamp));
+            Ok(commits)
+        })
+        .await
+    }
+
+    async fn latest_tag(&self, rev: &str) -> Result<Option<String>> {
+        let rev = rev.to_string();
+        self.with_repo(move |repo| {
+            let Ok(start) = resolve_commit(&repo, &rev) else {
+                return Ok(None);
+            };
+            let mut tagged = HashMap::new();
+            for reference in repo.references()?.tags()? {
+                let mut reference = reference.map_err(|e| anyhow!(e))?;
+                let name = reference.name().shorten().to_str_lossy().into_owned();
+                // 附注标签要剥到它指向的提交
+                if let Ok(commit) = reference.peel_to_commit() {
+                    tagged.entry(commit.id).or_insert(name);
+                }
+            }
+            // 按提交时间从新到旧，第一个带标签的祖先就是最近的标签
+            let ancestors = repo
+                .rev_walk([start])
+                .sorting(gix::revision::walk::Sorting::ByCommitTime(Default::default()))
+                .all()?;
+            for info in ancestors {
+                if let Some(name) = tagged.remove(&info?.id) {
+                    return Ok(Some(name));
+                }
+            }
+            Ok(None)
+        })
+        .await
+    }
+
+    async fn branch_commits(&self, filter: &LogFilter) -> Result<Vec<BranchCommit>> {
+        // `--author` 的正则语义和 `--source` 的分支归属都要与 git 一致，交给 git 进程处理
+        self.process.branch_commits(filter).await
+    }
+
+    async fn commit(&self, rev: &str) -> Result<CommitInfo> {
+        let rev = rev.to_string();
+        self.with_repo(move |repo| {
+            let id = resolve_commit(&repo, &rev)?;
+            commit_info(&repo, id)
+        })
+        .await
+    }
+
+    async fn patch_id(&self, sha: &str) -> Result<Option<String>> {
+        // patch-id 要与 git 的算法完全一致，交给 git 进程处理
+        self.process.patch_id(sha).await
+    }
+
+    async fn commit_stats(&self, sha: &str) -> Result<Vec<FileStat>> {
+        // 行数统计需要逐个文件做文本 diff，交给 git 进程处理
+        self.process.commit_stats(sha).await
+    }
+
+    async fn current_branch(&self) -> Result<Option<String>> {
+        self.with_repo(|repo| {
+            Ok(repo
+                .head_name()?
+                .map(|name| name.shorten().to_str_lossy().into_owned()))
+        })
+        .await
+    }
+
+    async fn merge_base(&self, one: &str, two: &str) -> Result<String> {
+        let one = one.to_string();
+        let two = two.to_string();
+        self.with_repo(move |repo| {
+            let one = resolve_commit(&repo, &one)?;
+            let two = resolve_commit(&repo, &two)?;
+            Ok(repo.merge_base(one, two)?.to_string())
+        })
+        .await
+    }
+
+    async fn refs(&self, prefix: &str) -> Result<Vec<String>> {
+        let prefix = prefix.to_string();
+        self.with_repo(move |repo| {
+            let mut refs = Vec::new();
+            for reference in repo.references()?.prefixed(prefix.as_str())? {
+                let reference = reference.map_err(|e| anyhow!(e))?;
+                refs.push(reference.name().as_bstr().to_str_lossy().into_owned());
+            }
+            Ok(refs)
+        })
+        .await
+    }
+
+    async fn note(&self, notes_ref: &str, sha: &str) -> Result<Option<String>> {
+        let notes_ref = notes_ref.to_string();
+        let sha = sha.to_string();
+        self.with_repo(move |repo| {
+            let Some(mut reference) = repo.try_find_reference(notes_ref.as_str())? else {
+                return Ok(None);
+            };
+            let tree = reference.peel_to_commit()?.tree()?;
+            // notes 较多时 git 会按前两位拆分目录 (fanout)
+            for path in [sha.clone(), format!("{}/{}", &sha[..2], &sha[2..])] {
+                if let Some(entry) = tree.lookup_entry_by_path(&path)? {
+                    let blob = entry.object()?;
+                    return Ok(Some(blob.data.to_str_lossy().trim_end().to_string()));
+                }
+            }
+            Ok(None)
+        })
+        .await
+    }
+
+    async fn config_value(&self, key: &str) -> Result<Option<String>> {
+        let key = key.to_string();
+        self.with_repo(move |repo| {
+            Ok(repo
+                .config_snapshot()
+                .string(key.as_str())
+                .map(|value| value.to_str_lossy().into_owned()))
+        })
+        .await
+    }
+}
+
+/// 把修订版本解析为提交，附注标签会被剥到它指向的提交
+fn resolve_commit(repo: &gix::Repository, rev: &str) -> Result<gix::ObjectId> {
+    Ok(repo.rev_parse_single(rev)?.object()?.peel_to_commit()?.id)
+}
+
+fn commit_info(repo: &gix::Repository, id: gix::ObjectId) -> Result<CommitInfo> {
+    let commit = repo.find_commit(id)?;
+    let author = commit.author()?;
+    Ok(CommitInfo {
+        sha: commit.id.to_string(),
+        message: commit.message_raw_sloppy().to_str_lossy().trim_end().to_string(),
+        author_name: author.name.to_str_lossy().into_owned(),
+        author_email: author.email.to_str_lossy().into_owned(),
+        timestamp: author.time()?.seconds,
+        commit_timestamp: commit.committer()?.time()?.seconds,
+    })
+}
+
+/// HEAD 的树和索引之间的变化，开启重命名检测
+fn staged_changes(repo: &gix::Repository) -> Result<Vec<ChangeRef<'static, 'static>>> {
+    let tree_id = repo.head_tree_id_or_empty()?;
+    let index = repo.index_or_empty()?;
+    let mut changes = Vec::new();
+    repo.tree_index_status(
+        &tree_id,
+        &index,
+        None,
+        TrackRenames::Given(Default::default()),
+        |change, _, _| {
+            changes.push(change.into_owned());
+            Ok::<_, std::convert::Infallible>(Action::Continue)
+        },
+    )?;
+    Ok(changes)
+}
+
+/// 生成与 `git diff --staged -M` 格式相同的补丁
+fn staged_diff(repo: &gix::Repository, context_lines: u32) -> Result<String> {
+    let mut output = String::new();
+    for change in staged_changes(repo)? {
+        let (old_path, new_path, old, new) = match &change {
+            ChangeRef::Addition {
+                location,
+                entry_mode,
+                id,
+                ..
+            } => (location, location, None, Some((*entry_mode, id.as_ref()))),
+            ChangeRef::Deletion {
+                location,
+                entry_mode,
+                id,
+                ..
+            } => (location, location, Some((*entry_mode, id.as_ref())), None),
+            ChangeRef::Modification {
+                location,
+                previous_entry_mode,
+                previous_id,
+                entry_mode,
+                id,
+                ..
+            } => (
+                location,
+                location,
+                Some((*previous_entry_mode, previous_id.as_ref())),
+                Some((*entry_mode, id.as_ref())),
+            ),
+            ChangeRef::Rewrite {
+                source_location,
+                source_entry_mode,
+                source_id,
+                location,
+                entry_mode,
+                id,
+                ..
+            } => (
+                source_location,
+                location,
+                Some((*source_entry_mode, source_id.as_ref())),
+                Some((*entry_mode, id.as_ref())),
+            ),
+        };
+        let old_path = old_path.to_str_lossy();
+        let new_path = new_path.to_str_lossy();
+
+        output.push_str(&format!("diff --git a/{old_path} b/{new_path}\n"));
+        match (old, new) {
+            (None, Some((mode, _))) => output.push_str(&format!("new file mode {}\n", mode_str(mode))),
+            (Some((mode, _)), None) => output.push_str(&format!("deleted file mode {}\n", mode_str(mode))),
+            (Some((old_mode, _)), Some((new_mode, _))) if old_mode != new_mode => output.push_str(
+                &format!("old mode {}\nnew mode {}\n", mode_str(old_mode), mode_str(new_mode)),
+            ),
+            _ => {}
+        }
+
+        let old_data = read_blob(repo, old)?;
+        let new_data = read_blob(repo, new)?;
+
+        if old_path != new_path {
+            output.push_str(&format!(
+                "similarity index {}%\nrename from {old_path}\nrename to {new_path}\n",
+                similarity(&old_data, &new_data)
+            ));
+        }
+
+        let is_submodule = [old, new]
+            .iter()
+            .flatten()
+            .any(|(mode, _)| mode.contains(Mode::COMMIT));
+        if is_submodule {
+            output.push_str("@@ -1 +1 @@\n");
+            if let Some((_, id)) = old {
+                output.push_str(&format!("-Subproject commit {id}\n"));
+            }
+            if let Some((_, id)) = new {
+                output.push_str(&format!("+Subproject commit {id}\n"));
+            }
+            continue;
+        }
+
+        if old_data == new_data {
+            continue;
+        }
+        let old_label = old.map_or("/dev/null".to_string(), |_| format!("a/{old_path}"));
+        let new_label = new.map_or("/dev/null".to_string(), |_| format!("b/{new_path}"));
+        if is_binary(&old_data) || is_binary(&new_data) {
+            output.push_str(&format!("Binary files {old_label} and {new_label} differ\n"));
+            continue;
+        }
+
+        output.push_str(&format!("--- {old_label}\n+++ {new_label}\n"));
+        output.push_str(&unified_diff(&old_data, &new_data, context_lines)?);
+    }
+    Ok(output)
+}
+
+fn read_blob(repo: &gix::Repository, side: Option<(Mode, &gix::oid)>) -> Result<Vec<u8>> {
+    match side {
+        Some((mode, id)) if !mode.contains(Mode::COMMIT) => {
+            Ok(repo.find_object(id)?.detach().data)
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
+fn mode_str(mode: Mode) -> String {
+    format!("{:06o}", mode.bits())
+}
+
+fn is_binary(data: &[u8]) -> bool {
+    data[..data.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+fn unified_diff(old: &[u8], new: &[u8], context_lines: u32) -> Result<String> {
+    let input = InternedInput::new(old, new);
+    let diff = gix::diff::blob::diff(
+        Algorithm::Histogram,
+        &input,
+        UnifiedDiff::new(
+            &input,
+            ConsumeBinaryHunk::new(String::new(), "\n"),
+            ContextSize::symmetrical(context_lines),
+        ),
+    )?;
+    Ok(diff)
+}
+
+/// 按行估算两个版本的相似度 (百分比)，与 git 的按字节计算略有差异
+fn similarity(old: &[u8], new: &[u8]) -> u8 {
+    if old == new {
+        return 100;
+    }
+    let input = InternedInput::new(old, new);
+    let total = input.before.len() + input.after.len();
+    if total == 0 {
+        return 100;
+    }
+    let counter = gix::diff::blob::diff(
+        Algorithm::Histogram,
+        &input,
+        gix::diff::blob::sink::Counter::default(),
+    );
+    let changed = counter.removals as usize + counter.insertions as usize;
+    (100 * total.saturating_sub(changed) / total) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gix::objs::tree::{Entry, EntryKind};
+
+    /// 只用 gix 写对象和引用的夹具仓库，不依赖系统中的 `git`
+    struct Fixture {
+        _dir: tempfile::TempDir,
+        repo: gix::Repository,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let repo = gix::init(dir.path()).unwrap();
+            std::fs::write(
+                repo.git_dir().join("config"),
+                "[core]\n\trepositoryformatversion = 0\n\tbare = false\n[matecode]\n\tfixture = yes\n",
+            )
+            .unwrap();
+            let repo = gix::open(dir.path()).unwrap();
+            Fixture { _dir: dir, repo }
+        }
+
+        fn tree(&self, files: &[(&str, &str)]) -> gix::ObjectId {
+            let mut entries: Vec<Entry> = files
+                .iter()
+                .map(|(name, content)| Entry {
+                    mode: EntryKind::Blob.into(),
+                    filename: (*name).into(),
+                    oid: self.repo.write_blob(content.as_bytes()).unwrap().detach(),
+                })
+                .collect();
+            entries.sort();
+            self.repo
+                .write_object(gix::objs::Tree { entries })
+                .unwrap()
+                .detach()
+        }
+
+        fn commit(
+            &self,
+            reference: &str,
+            message: &str,
+            files: &[(&str, &str)],
+            parents: &[gix::ObjectId],
+            seconds: i64,
+        ) -> gix::ObjectId {
+            let time = gix::date::Time::new(seconds, 0);
+            let signature = gix::actor::SignatureRef {
+                name: "Fixture".into(),
+                email: "fixture@example.com".into(),
+                time: &time.to_string(),
+            };
+            self.repo
+                .commit_as(
+                    signature,
+                    signature,
+                    reference,
+                    message,
+                    self.tree(files),
+                    parents.iter().copied(),
+                )
+                .unwrap()
+                .detach()
+        }
+
+        fn backend(&self) -> GixBackend {
+            GixBackend::discover(self.repo.workdir().unwrap()).unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_log_refs_notes_and_config_from_fixture_repo() {
+        let fixture = Fixture::new();
+        let first = fixture.commit(
+            "HEAD",
+            "feat: first",
+            &[("a.txt", "a\n")],
+            &[],
+            1_700_000_000,
+        );
+        let second = fixture.commit(
+            "HEAD",
+            "fix: second\n\nbody",
+            &[("a.txt", "a\n"), ("b.txt", "b\n")],
+            &[first],
+            1_700_000_100,
+        );
+        fixture
+            .repo
+            .tag_reference("v1.0.0", first, gix::refs::transaction::PreviousValue::Any)
+            .unwrap();
+        // git notes 把 note 存成以提交 sha 命名的文件
+        fixture.commit(
+            "refs/notes/commits",
+            "Notes added by 'git notes add'",
+            &[(&second.to_string(), "reviewed\n")],
+            &[],
+            1_700_000_200,
+        );
+
+        let backend = fixture.backend();
+
+        let log = backend.log(5).await.unwrap();
+        let subjects: Vec<_> = log.iter().map(CommitInfo::subject).collect();
+        assert_eq!(subjects, ["fix: second", "feat: first"]);
+        assert_eq!(log[0].message, "fix: second\n\nbody");
+        assert_eq!(log[0].author_name, "Fixture");
+        assert_eq!(log[0].commit_timestamp, 1_700_000_100);
+
+        let history = backend.file_history("b.txt", 5).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].sha, second.to_string());
+
+        assert_eq!(
+            backend.refs("refs/tags/").await.unwrap(),
+            ["refs/tags/v1.0.0"]
+        );
+        assert_eq!(
+            backend.latest_tag("HEAD").await.unwrap().as_deref(),
+            Some("v1.0.0")
+        );
+        assert_eq!(
+            backend
+                .note("refs/notes/commits", &second.to_string())
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("reviewed")
+        );
+        assert_eq!(
+            backend
+                .note("refs/notes/commits", &first.to_string())
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            backend
+                .config_value("matecode.fixture")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("yes")
+        );
+    }
+}
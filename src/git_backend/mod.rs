@@ -0,0 +1,279 @@
+//! src/git_backend/mod.rs
+//!
+//! matecode 读取仓库信息的统一入口。默认通过 `git` 进程完成，
+//! 也可以在配置中设置 `git_backend = "gix"` 改用进程内的纯 Rust 实现。
+
+mod in_process;
+mod process;
+
+use crate::config::{self, GitBackendKind};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+
+pub use in_process::GixBackend;
+pub use process::ProcessBackend;
+
+/// 一条提交记录
+#[derive(Debug, Clone)]
+pub struct CommitInfo {
+    pub sha: String,
+    /// 完整的提交信息
+    pub message: String,
+    pub author_name: String,
+    pub author_email: String,
+    /// 作者时间 (Unix 时间戳，秒)
+    pub timestamp: i64,
+    /// 提交者时间，rebase 和 amend 之后会与作者时间不同
+    pub commit_timestamp: i64,
+}
+
+impl CommitInfo {
+    pub fn subject(&self) -> &str {
+        self.message.lines().next().unwrap_or_default()
+    }
+
+    pub fn short_sha(&self) -> &str {
+        &self.sha[..self.sha.len().min(7)]
+    }
+}
+
+/// 一个提交中单个文件的增删行数，二进制文件没有行数
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStat {
+    pub path: String,
+    pub additions: Option<usize>,
+    pub deletions: Option<usize>,
+}
+
+/// 本地分支上的一个提交，以及最先到达它的分支
+#[derive(Debug, Clone)]
+pub struct BranchCommit {
+    pub commit: CommitInfo,
+    pub branch: Option<String>,
+}
+
+/// 导入历史提交时的筛选条件
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    /// 只包含提交时间不早于这个时间的提交 (Unix 时间戳，秒)
+    pub since: Option<i64>,
+    /// 作者名或邮箱匹配的正则表达式，与 `git log --author` 相同
+    pub author: Option<String>,
+}
+
+/// 要描述的改动来自哪里
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DiffSource {
+    /// 暂存区相对 HEAD 的改动
+    #[default]
+    Staged,
+    /// 工作区中尚未暂存的改动
+    WorkingTree,
+    /// 两个提交之间的改动，例如 `main..feature`
+    Range(String),
+}
+
+/// 改动的来源，以及可选的 pathspec 限制
+#[derive(Debug, Clone, Default)]
+pub struct DiffScope {
+    pub source: DiffSource,
+    /// 与 git 的 pathspec 语法相同，相对于当前目录；为空时不限制
+    pub pathspec: Vec<String>,
+}
+
+impl DiffScope {
+    /// 用于提示信息，例如 "暂存的 diff"
+    pub fn describe(&self) -> String {
+        let source = match &self.source {
+            DiffSource::Staged => "暂存的 diff".to_string(),
+            DiffSource::WorkingTree => "工作区的 diff".to_string(),
+            DiffSource::Range(range) => format!("{range} 的 diff"),
+        };
+        if self.pathspec.is_empty() {
+            source
+        } else {
+            format!("{source} ({})", self.pathspec.join(" "))
+        }
+    }
+}
+
+/// 生成 diff 时保留多少上下文
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// 每处改动前后保留的行数，与 `git diff -U<n>` 相同
+    pub context_lines: u32,
+    /// 把改动所在的整个函数作为上下文，与 `git diff -W` 相同
+    pub function_context: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            context_lines: 3,
+            function_context: false,
+        }
+    }
+}
+
+/// matecode 需要的只读 git 操作
+#[async_trait]
+pub trait GitBackend: Send + Sync {
+    /// 工作区根目录
+    async fn toplevel(&self) -> Result<PathBuf>;
+
+    /// 当前工作区的 git 目录 (绝对路径)
+    async fn git_dir(&self) -> Result<PathBuf>;
+
+    /// 多个工作区共享的 git 目录 (绝对路径)，hooks 和 notes 都在这里
+    async fn common_dir(&self) -> Result<PathBuf>;
+
+    /// `scope` 范围内的 diff，格式与 `git diff -M --patch` 相同，
+    /// 开头可以带有 `--numstat` 统计行
+    async fn diff(&self, scope: &DiffScope, options: &DiffOptions) -> Result<String>;
+
+    /// 暂存区中有变化的文件
+    async fn staged_files(&self) -> Result<Vec<String>>;
+
+    /// 被 git 跟踪的所有文件，相对于仓库根目录
+    async fn tracked_files(&self) -> Result<Vec<String>>;
+
+    /// 从 HEAD 开始的最近 `limit` 条提交
+    async fn log(&self, limit: usize) -> Result<Vec<CommitInfo>>;
+
+    /// 从 HEAD 开始修改过 `path` (相对于仓库根目录) 的最近 `limit` 条提交
+    async fn file_history(&self, path: &str, limit: usize) -> Result<Vec<CommitInfo>>;
+
+    /// 所有本地分支上符合条件的非合并提交，从新到旧，附带最先到达该提交的分支名
+    async fn branch_commits(&self, filter: &LogFilter) -> Result<Vec<BranchCommit>>;
+
+    /// 单个提交的信息，`rev` 可以是 sha、分支或标签
+    async fn commit(&self, rev: &str) -> Result<CommitInfo>;
+
+    /// 提交的稳定 patch-id (`git patch-id --stable`)，内容相同的 cherry-pick 和 rebase 结果相同；
+    /// 合并提交和空提交没有 patch-id
+    async fn patch_id(&self, sha: &str) -> Result<Option<String>>;
+
+    /// 提交相对第一个父提交的 `--numstat`，根提交相对空树
+    async fn commit_stats(&self, sha: &str) -> Result<Vec<FileStat>>;
+
+    /// `base..head` 中的提交 (可以从 `head` 到达、但不能从 `base` 到达)，从新到旧；
+    /// `base` 为空时返回 `head` 的全部历史
+    async fn commits_between(&self, base: Option<&str>, head: &str) -> Result<Vec<CommitInfo>>;
+
+    /// 从 `rev` 可以到达的最近的标签名 (与 `git describe --tags --abbrev=0` 相同)
+    async fn latest_tag(&self, rev: &str) -> Result<Option<String>>;
+
+    /// 当前分支的短名称，例如 `feature/PROJ-123-login`；分离 HEAD 时为 `None`
+    async fn current_branch(&self) -> Result<Option<String>>;
+
+    /// 两个修订版本的最佳公共祖先，返回完整的 sha
+    async fn merge_base(&self, one: &str, two: &str) -> Result<String>;
+
+    /// 以 `prefix` 开头的引用的完整名称，例如 `refs/tags/`
+    async fn refs(&self, prefix: &str) -> Result<Vec<String>>;
+
+    /// `notes_ref` (如 `refs/notes/commits`) 中附加在某个提交上的 note
+    async fn note(&self, notes_ref: &str, sha: &str) -> Result<Option<String>>;
+
+    /// 读取一个配置项，例如 `core.hooksPath`
+    async fn config_value(&self, key: &str) -> Result<Option<String>>;
+}
+
+/// 解析后的仓库位置。在子目录、关联工作区 (worktree) 和子模块中运行时都指向正确的目录，
+/// 所有命令都应该通过它定位仓库，而不是依赖当前目录。
+#[derive(Debug, Clone)]
+pub struct RepoContext {
+    /// 工作区根目录
+    pub toplevel: PathBuf,
+    /// 当前工作区的 git 目录；关联工作区中是 `<主仓库>/.git/worktrees/<名称>`
+    pub git_dir: PathBuf,
+    /// 所有工作区共享的 git 目录
+    pub common_dir: PathBuf,
+}
+
+impl RepoContext {
+    /// 是否是 `git worktree add` 创建的关联工作区
+    pub fn is_linked_worktree(&self) -> bool {
+        self.git_dir != self.common_dir
+    }
+
+    /// 项目名称。关联工作区使用主仓库的名称，这样各个工作区的记录会归到同一个项目下
+    pub fn project_name(&self) -> String {
+        let dir_name = |path: &Path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map(String::from)
+        };
+        let name = if self.is_linked_worktree() {
+            // 普通仓库的共享目录是 `<主仓库>/.git`，裸仓库则是 `<名称>.git`
+            match dir_name(&self.common_dir).as_deref() {
+                Some(".git") => self.common_dir.parent().and_then(dir_name),
+                Some(name) => Some(name.trim_end_matches(".git").to_string()),
+                None => None,
+            }
+        } else {
+            dir_name(&self.toplevel)
+        };
+        name.unwrap_or_else(|| "unknown_project".to_string())
+    }
+
+    /// hooks 目录：优先使用 `core.hooksPath` (相对路径以工作区根目录为基准)，
+    /// 否则是共享 git 目录下的 `hooks`
+    pub async fn hooks_dir(&self) -> Result<PathBuf> {
+        match backend().await?.config_value("core.hooksPath").await? {
+            Some(hooks_path) => Ok(self.toplevel.join(hooks_path)),
+            None => Ok(self.common_dir.join("hooks")),
+        }
+    }
+}
+
+static BACKEND: OnceCell<Arc<dyn GitBackend>> = OnceCell::const_new();
+static REPO: OnceCell<RepoContext> = OnceCell::const_new();
+
+/// 根据配置返回当前进程共用的 backend，在当前目录中解析仓库；没有配置文件时使用 `git` 进程。
+pub async fn backend() -> Result<Arc<dyn GitBackend>> {
+    BACKEND
+        .get_or_try_init(|| async { open_backend(&std::env::current_dir()?).await })
+        .await
+        .cloned()
+}
+
+/// 当前仓库的位置，第一次调用时解析并缓存
+pub async fn repo_context() -> Result<&'static RepoContext> {
+    REPO.get_or_try_init(|| async { resolve_repo(backend().await?.as_ref()).await })
+        .await
+}
+
+/// `dir` 所在仓库的 backend 和位置，不使用也不影响当前进程共用的那一份。
+/// 用于在一个进程中依次处理多个仓库，例如 `archive --backfill --repo a --repo b`
+pub async fn open(dir: &Path) -> Result<(Arc<dyn GitBackend>, RepoContext)> {
+    let backend = open_backend(dir).await?;
+    let repo = resolve_repo(backend.as_ref()).await?;
+    Ok((backend, repo))
+}
+
+async fn open_backend(dir: &Path) -> Result<Arc<dyn GitBackend>> {
+    let kind = config::load_config()
+        .await
+        .map(|config| config.git_backend)
+        .unwrap_or_default();
+    let backend: Arc<dyn GitBackend> = match kind {
+        GitBackendKind::Process => Arc::new(ProcessBackend::new(dir)),
+        GitBackendKind::Gix => Arc::new(GixBackend::discover(dir)?),
+    };
+    Ok(backend)
+}
+
+async fn resolve_repo(backend: &dyn GitBackend) -> Result<RepoContext> {
+    // 统一成规范路径，去掉 `..` 和符号链接，方便比较
+    let canonical = |path: PathBuf| path.canonicalize().unwrap_or(path);
+    Ok(RepoContext {
+        toplevel: canonical(backend.toplevel().await?),
+        git_dir: canonical(backend.git_dir().await?),
+        common_dir: canonical(backend.common_dir().await?),
+    })
+}
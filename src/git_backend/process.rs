@@ -0,0 +1,303 @@
+//! src/git_backend/process.rs
+//!
+//! 通过启动 `git` 进程实现 [`GitBackend`]。每个命令都在创建时指定的目录中运行，
+//! 与 `git -C <目录>` 相同。
+
+use super::{
+    BranchCommit, CommitInfo, DiffOptions, DiffScope, DiffSource, FileStat, GitBackend, LogFilter,
+};
+use crate::git::{run_git_command, run_git_command_with_input};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use std::path::PathBuf;
+
+/// 字段和记录之间的分隔符，不会出现在正常的提交信息中
+const FIELD_SEP: char = '\u{1f}';
+const RECORD_SEP: char = '\u{1e}';
+
+pub struct ProcessBackend {
+    dir: PathBuf,
+}
+
+impl ProcessBackend {
+    /// `dir` 可以是工作区中的任意目录，相对路径以当前目录为基准
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// 在 `dir` 中运行 git 命令
+    async fn git(&self, args: &[&str]) -> Result<String> {
+        let dir = self.dir.to_string_lossy();
+        let args: Vec<&str> = ["-C", &dir]
+            .into_iter()
+            .chain(args.iter().copied())
+            .collect();
+        run_git_command(&args).await
+    }
+}
+
+#[async_trait]
+impl GitBackend for ProcessBackend {
+    async fn toplevel(&self) -> Result<PathBuf> {
+        let output = self.git(&["rev-parse", "--show-toplevel"]).await?;
+        Ok(PathBuf::from(output.trim()))
+    }
+
+    async fn git_dir(&self) -> Result<PathBuf> {
+        let output = self.git(&["rev-parse", "--absolute-git-dir"]).await?;
+        Ok(PathBuf::from(output.trim()))
+    }
+
+    async fn common_dir(&self) -> Result<PathBuf> {
+        let output = self.git(&["rev-parse", "--git-common-dir"]).await?;
+        // 可能是相对于运行目录的路径，例如在子目录中是 `../.git`
+        Ok(std::env::current_dir()?.join(&self.dir).join(output.trim()))
+    }
+
+    async fn diff(&self, scope: &DiffScope, options: &DiffOptions) -> Result<String> {
+        let unified = format!("-U{}", options.context_lines);
+        // 解析依赖 `a/` `b/` 前缀和纯文本输出，不受用户的 diff 和颜色配置影响
+        let mut args = vec![
+            "-c",
+            "core.quotePath=false",
+            "diff",
+            "--no-ext-diff",
+            "--no-color",
+            "--no-relative",
+            "--src-prefix=a/",
+            "--dst-prefix=b/",
+            "-M",
+            "--numstat",
+            "--patch",
+            &unified,
+        ];
+        if options.function_context {
+            args.push("--function-context");
+        }
+        match &scope.source {
+            DiffSource::Staged => args.push("--staged"),
+            DiffSource::WorkingTree => {}
+            DiffSource::Range(range) => args.push(range),
+        }
+        args.push("--");
+        args.extend(scope.pathspec.iter().map(String::as_str));
+        self.git(&args).await
+    }
+
+    async fn staged_files(&self) -> Result<Vec<String>> {
+        let output = self
+            .git(&[
+                "-c",
+                "core.quotePath=false",
+                "diff",
+                "--no-ext-diff",
+                "--no-color",
+                "--no-relative",
+                "--staged",
+                "--name-only",
+            ])
+            .await?;
+        Ok(output.lines().map(String::from).collect())
+    }
+
+    async fn tracked_files(&self) -> Result<Vec<String>> {
+        let toplevel = self.toplevel().await?;
+        let output = self
+            .git(&["-C", &toplevel.to_string_lossy(), "ls-files", "-z"])
+            .await?;
+        Ok(output
+            .split('\0')
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect())
+    }
+
+    async fn log(&self, limit: usize) -> Result<Vec<CommitInfo>> {
+        let limit = format!("-{limit}");
+        // 空仓库没有 HEAD，视为没有提交
+        let Ok(output) = self.git(&["log", &limit, &log_format()]).await else {
+            return Ok(Vec::new());
+        };
+        Ok(parse_log(&output))
+    }
+
+    async fn file_history(&self, path: &str, limit: usize) -> Result<Vec<CommitInfo>> {
+        let toplevel = self.toplevel().await?;
+        let limit = format!("-{limit}");
+        // 空仓库没有 HEAD，视为没有提交
+        let Ok(output) = self
+            .git(&[
+                "--literal-pathspecs",
+                "-C",
+                &toplevel.to_string_lossy(),
+                "log",
+                &limit,
+                &log_format(),
+                "--",
+                path,
+            ])
+            .await
+        else {
+            return Ok(Vec::new());
+        };
+        Ok(parse_log(&output))
+    }
+
+    async fn commits_between(&self, base: Option<&str>, head: &str) -> Result<Vec<CommitInfo>> {
+        let range = match base {
+            Some(base) => format!("{base}..{head}"),
+            None => head.to_string(),
+        };
+        let output = self.git(&["log", &log_format(), &range, "--"]).await?;
+        Ok(parse_log(&output))
+    }
+
+    async fn latest_tag(&self, rev: &str) -> Result<Option<String>> {
+        // 没有可以到达的标签时 git describe 以非零状态码退出
+        Ok(self
+            .git(&["describe", "--tags", "--abbrev=0", rev])
+            .await
+            .ok()
+            .map(|tag| tag.trim().to_string()))
+    }
+
+    async fn branch_commits(&self, filter: &LogFilter) -> Result<Vec<BranchCommit>> {
+        // 合并提交不会触发 post-commit 钩子，导入时同样跳过
+        let mut args = vec![
+            "log".to_string(),
+            "--branches".to_string(),
+            "--no-merges".to_string(),
+            "--source".to_string(),
+            format!("--pretty=format:%S{FIELD_SEP}{}", log_fields()),
+        ];
+        if let Some(since) = filter.since {
+            args.push(format!("--since=@{since}"));
+        }
+        if let Some(author) = &filter.author {
+            args.push(format!("--author={author}"));
+        }
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let output = self.git(&args).await?;
+
+        Ok(output
+            .split(RECORD_SEP)
+            .filter_map(|record| {
+                let (source, record) = record.trim_start_matches('\n').split_once(FIELD_SEP)?;
+                // `--branches` 给出的通常是短名称
+                let branch = source.strip_prefix("refs/heads/").unwrap_or(source);
+                let branch = (!branch.is_empty()).then(|| branch.to_string());
+                Some(BranchCommit {
+                    commit: parse_commit(record)?,
+                    branch,
+                })
+            })
+            .collect())
+    }
+
+    async fn commit(&self, rev: &str) -> Result<CommitInfo> {
+        let output = self.git(&["log", "-1", &log_format(), rev, "--"]).await?;
+        parse_log(&output)
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("找不到提交: {rev}"))
+    }
+
+    async fn patch_id(&self, sha: &str) -> Result<Option<String>> {
+        // 不带 -m 时 diff-tree 不输出合并提交的 diff，patch-id 的输出也就为空
+        let patch = self
+            .git(&["diff-tree", "-p", "--root", "--no-color", sha])
+            .await?;
+        let output = run_git_command_with_input(&["patch-id", "--stable"], &patch).await?;
+        Ok(output.split_whitespace().next().map(String::from))
+    }
+
+    async fn commit_stats(&self, sha: &str) -> Result<Vec<FileStat>> {
+        // 合并提交只统计相对第一个父提交的变化
+        let output = self
+            .git(&[
+                "show",
+                "--numstat",
+                "--format=",
+                "-M",
+                "--diff-merges=first-parent",
+                sha,
+            ])
+            .await?;
+        Ok(output.lines().filter_map(parse_numstat).collect())
+    }
+
+    async fn current_branch(&self) -> Result<Option<String>> {
+        // 分离 HEAD 时 git symbolic-ref -q 以状态码 1 退出
+        Ok(self
+            .git(&["symbolic-ref", "--short", "-q", "HEAD"])
+            .await
+            .ok()
+            .map(|branch| branch.trim().to_string()))
+    }
+
+    async fn merge_base(&self, one: &str, two: &str) -> Result<String> {
+        let output = self.git(&["merge-base", one, two]).await?;
+        Ok(output.trim().to_string())
+    }
+
+    async fn refs(&self, prefix: &str) -> Result<Vec<String>> {
+        let output = self
+            .git(&["for-each-ref", "--format=%(refname)", prefix])
+            .await?;
+        Ok(output.lines().map(String::from).collect())
+    }
+
+    async fn note(&self, notes_ref: &str, sha: &str) -> Result<Option<String>> {
+        Ok(self
+            .git(&["notes", "--ref", notes_ref, "show", sha])
+            .await
+            .ok()
+            .map(|note| note.trim_end().to_string()))
+    }
+
+    async fn config_value(&self, key: &str) -> Result<Option<String>> {
+        // 配置项不存在时 git config 以状态码 1 退出
+        Ok(self
+            .git(&["config", "--get", key])
+            .await
+            .ok()
+            .map(|value| value.trim().to_string()))
+    }
+}
+
+fn log_format() -> String {
+    format!("--pretty=format:{}", log_fields())
+}
+
+/// 每条提交输出的字段，完整的提交信息放在最后
+fn log_fields() -> String {
+    format!("%H{FIELD_SEP}%an{FIELD_SEP}%ae{FIELD_SEP}%at{FIELD_SEP}%ct{FIELD_SEP}%B{RECORD_SEP}")
+}
+
+/// `12\t3\tpath`，二进制文件的行数为 `-`
+fn parse_numstat(line: &str) -> Option<FileStat> {
+    let mut fields = line.splitn(3, '\t');
+    let additions = fields.next()?;
+    let deletions = fields.next()?;
+    Some(FileStat {
+        additions: additions.parse().ok(),
+        deletions: deletions.parse().ok(),
+        path: fields.next()?.to_string(),
+    })
+}
+
+fn parse_log(output: &str) -> Vec<CommitInfo> {
+    output.split(RECORD_SEP).filter_map(parse_commit).collect()
+}
+
+fn parse_commit(record: &str) -> Option<CommitInfo> {
+    let mut fields = record.trim_start_matches('\n').splitn(6, FIELD_SEP);
+    Some(CommitInfo {
+        sha: fields.next()?.to_string(),
+        author_name: fields.next()?.to_string(),
+        author_email: fields.next()?.to_string(),
+        timestamp: fields.next()?.parse().ok()?,
+        commit_timestamp: fields.next()?.parse().ok()?,
+        message: fields.next()?.trim_end().to_string(),
+    })
+}
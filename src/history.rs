@@ -1,11 +1,193 @@
 //! src/history.rs
+//!
+//! 归档的提交历史。每个项目一个 `history/<项目>/commits.jsonl`，每行一条提交记录，
+//! 保留 sha、分支、作者、时间、trailer 和文件统计，供 `report` 等命令使用。
+//!
+//! 记录以 sha 为键：同一个提交重复归档时替换旧记录，amend 或 rebase 之前的版本由
+//! `post-rewrite` 钩子替换成新的版本；报告中 patch-id 相同的提交 (例如在多个分支上的
+//! cherry-pick) 只出现一次。
+//!
+//! 旧版本把提交信息按天追加到 `history/<项目>/<日期>.md` 中并用 `---` 分隔。
+//! 第一次读写某个项目的历史时会把这些文件迁移进来，原文件移到 `migrated/` 目录中保留。
+//!
+//! 钩子和 `report` 等命令可能同时运行，迁移和修改存储文件之前都要先拿到项目目录下的
+//! `commits.lock`。
 
 use crate::config::get_config_dir;
-use anyhow::{Context, Result};
-use chrono::NaiveDate;
-use std::collections::BTreeMap;
-use std::path::PathBuf;
+use crate::git_backend::FileStat;
+use crate::issue;
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+pub const STORE_FILE: &str = "commits.jsonl";
+const LOCK_FILE: &str = "commits.lock";
+/// 等待其他进程释放锁的最长时间
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+/// 超过这个时间的锁文件是崩溃的进程留下的，可以直接删除
+const STALE_LOCK_AGE: Duration = Duration::from_secs(60);
+/// 迁移完成的旧文件移到这里，不会被再次迁移
+const MIGRATED_DIR: &str = "migrated";
+const LEGACY_SEPARATOR: &str = "\n\n---\n\n";
+const LEGACY_NOTES_MARKER: &str = "\n\nNotes:\n";
+
+/// 提交信息末尾的一个 trailer，例如 `Issue: PROJ-123`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trailer {
+    pub key: String,
+    pub value: String,
+}
+
+impl Trailer {
+    /// 提交信息末尾的所有 trailer
+    pub fn parse_all(message: &str) -> Vec<Trailer> {
+        issue::parse_trailers(message)
+            .into_iter()
+            .map(|(key, value)| Trailer { key, value })
+            .collect()
+    }
+}
+
+/// 一条归档的提交
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitRecord {
+    /// 从旧的 Markdown 历史迁移的记录没有 sha 和其他 git 信息
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha: Option<String>,
+    /// `git patch-id --stable`，合并提交没有 patch-id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patch_id: Option<String>,
+    /// 提交所在的工作区根目录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo_path: Option<PathBuf>,
+    pub project: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(default)]
+    pub author_name: String,
+    #[serde(default)]
+    pub author_email: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authored_at: Option<DateTime<FixedOffset>>,
+    /// 报告按这个时间的本地日期筛选
+    pub committed_at: DateTime<FixedOffset>,
+    pub archived_at: DateTime<FixedOffset>,
+    pub message: String,
+    #[serde(default)]
+    pub trailers: Vec<Trailer>,
+    #[serde(default)]
+    pub files: Vec<FileStat>,
+    /// 附加在提交上的 git notes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+impl CommitRecord {
+    /// 报告中使用的文本：提交信息，以及附加的 notes
+    pub fn report_text(&self) -> String {
+        match &self.notes {
+            Some(notes) => format!("{}{LEGACY_NOTES_MARKER}{notes}", self.message),
+            None => self.message.clone(),
+        }
+    }
+
+    fn committed_on(&self) -> NaiveDate {
+        self.committed_at.with_timezone(&Local).date_naive()
+    }
+
+    /// 是否是同一个提交：sha 相同，或者在同一个仓库的同一个分支上，作者时间和 patch-id 都相同
+    /// (amend 或 rebase 之前的版本；它们保留作者时间，只修改提交信息时 patch-id 也不变)，
+    /// 或者是同一天、提交信息相同的迁移记录
+    fn same_commit(&self, other: &CommitRecord) -> bool {
+        if self.sha.is_some() && self.sha == other.sha {
+            return true;
+        }
+        // 从旧的 Markdown 历史迁移的记录没有 sha，同一天、提交信息相同时视为同一个提交
+        if self.sha.is_none() != other.sha.is_none() {
+            return self.project == other.project
+                && self.committed_on() == other.committed_on()
+                && self.message.trim() == other.message.trim();
+        }
+        self.patch_id.is_some()
+            && self.patch_id == other.patch_id
+            && self.authored_at.is_some()
+            && self.authored_at == other.authored_at
+            && self.repo_path == other.repo_path
+            && self.branch == other.branch
+    }
+}
+
+/// Unix 时间戳转换为本地时区的时间
+pub fn local_time(timestamp: i64) -> DateTime<FixedOffset> {
+    Local
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .unwrap_or_else(Local::now)
+        .fixed_offset()
+}
+
+/// 一个项目的存储文件的独占锁，释放时删除锁文件
+struct ProjectLock {
+    path: PathBuf,
+}
+
+impl ProjectLock {
+    async fn acquire(project_dir: &Path) -> Result<Self> {
+        if !project_dir.exists() {
+            fs::create_dir_all(project_dir)
+                .await
+                .context("Failed to create project history directory")?;
+        }
+        let path = project_dir.join(LOCK_FILE);
+        let started = Instant::now();
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .await
+            {
+                Ok(_) => return Ok(ProjectLock { path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    if is_stale(&path).await {
+                        let _ = fs::remove_file(&path).await;
+                        continue;
+                    }
+                    if started.elapsed() > LOCK_TIMEOUT {
+                        return Err(anyhow!(
+                            "{} 被另一个 matecode 进程占用。如果没有其他 matecode 在运行，请删除这个文件后重试。",
+                            path.display()
+                        ));
+                    }
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+                Err(e) => return Err(e).with_context(|| format!("无法创建 {}", path.display())),
+            }
+        }
+    }
+}
+
+impl Drop for ProjectLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+async fn is_stale(path: &Path) -> bool {
+    fs::metadata(path)
+        .await
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|modified| {
+            SystemTime::now()
+                .duration_since(modified)
+                .is_ok_and(|age| age > STALE_LOCK_AGE)
+        })
+}
 
 pub async fn get_history_dir() -> Result<PathBuf> {
     let config_dir = get_config_dir().await?;
@@ -18,53 +200,293 @@ pub async fn get_history_dir() -> Result<PathBuf> {
     Ok(history_dir)
 }
 
+/// 保存一条记录。同一个提交已经归档过时替换旧记录，否则追加到末尾
+pub async fn save_record(record: CommitRecord) -> Result<()> {
+    let project_dir = get_history_dir().await?.join(&record.project);
+    let _lock = ProjectLock::acquire(&project_dir).await?;
+    migrate_legacy_history(&project_dir, &record.project).await?;
+    let mut records = read_records(&project_dir.join(STORE_FILE)).await?;
+    if !records.iter().any(|existing| existing.same_commit(&record)) {
+        return append_lines(&project_dir, std::slice::from_ref(&record)).await;
+    }
+    upsert(&mut records, record);
+    write_records(&project_dir, &records).await
+}
+
+/// 批量保存同一个项目的记录 (例如从 git log 导入)，规则与 [`save_record`] 相同
+pub async fn save_records(project: &str, new_records: Vec<CommitRecord>) -> Result<()> {
+    let project_dir = get_history_dir().await?.join(project);
+    let _lock = ProjectLock::acquire(&project_dir).await?;
+    migrate_legacy_history(&project_dir, project).await?;
+    let mut records = read_records(&project_dir.join(STORE_FILE)).await?;
+    for record in new_records {
+        upsert(&mut records, record);
+    }
+    write_records(&project_dir, &records).await
+}
+
+/// 一个项目已经归档的记录
+pub async fn load_records(project: &str) -> Result<Vec<CommitRecord>> {
+    let project_dir = get_history_dir().await?.join(project);
+    if !project_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let _lock = ProjectLock::acquire(&project_dir).await?;
+    migrate_legacy_history(&project_dir, project).await?;
+    read_records(&project_dir.join(STORE_FILE)).await
+}
+
+/// 把被改写的提交 (`post-rewrite` 钩子给出的旧 sha) 的记录替换成改写后的记录
+pub async fn replace_rewritten(
+    project: &str,
+    rewritten: Vec<(String, CommitRecord)>,
+) -> Result<()> {
+    let project_dir = get_history_dir().await?.join(project);
+    let _lock = ProjectLock::acquire(&project_dir).await?;
+    migrate_legacy_history(&project_dir, project).await?;
+    let mut records = read_records(&project_dir.join(STORE_FILE)).await?;
+    for (old_sha, record) in rewritten {
+        records.retain(|existing| existing.sha.as_deref() != Some(old_sha.as_str()));
+        upsert(&mut records, record);
+    }
+    write_records(&project_dir, &records).await
+}
+
+/// 替换第一条相同的记录并去掉其余相同的记录，没有时追加
+fn upsert(records: &mut Vec<CommitRecord>, record: CommitRecord) {
+    match records
+        .iter()
+        .position(|existing| existing.same_commit(&record))
+    {
+        Some(index) => {
+            let mut position = 0;
+            records.retain(|existing| {
+                let keep = position <= index || !existing.same_commit(&record);
+                position += 1;
+                keep
+            });
+            records[index] = record;
+        }
+        None => records.push(record),
+    }
+}
+
+/// 所有项目的记录，按项目名称分组，每组按归档顺序排列
+pub async fn load_all_records() -> Result<BTreeMap<String, Vec<CommitRecord>>> {
+    let history_dir = get_history_dir().await?;
+    let mut all_records = BTreeMap::new();
+
+    let mut project_entries = fs::read_dir(history_dir)
+        .await
+        .context("Failed to read history directory")?;
+    while let Some(project_entry) = project_entries.next_entry().await? {
+        let project_dir = project_entry.path();
+        if !project_dir.is_dir() {
+            continue;
+        }
+        let project_name = project_entry.file_name().to_string_lossy().to_string();
+        let lock = ProjectLock::acquire(&project_dir).await?;
+        migrate_legacy_history(&project_dir, &project_name).await?;
+        let records = read_records(&project_dir.join(STORE_FILE)).await?;
+        drop(lock);
+        if !records.is_empty() {
+            all_records.insert(project_name, records);
+        }
+    }
+
+    Ok(all_records)
+}
+
+/// 提交日期在 `start_date..=end_date` 之间的提交，按项目分组
 pub async fn get_all_commits_in_range(
     start_date: NaiveDate,
     end_date: NaiveDate,
 ) -> Result<BTreeMap<String, Vec<String>>> {
-    let history_dir = get_history_dir().await?;
     let mut all_projects_commits: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (project_name, mut records) in load_all_records().await? {
+        records.retain(|record| (start_date..=end_date).contains(&record.committed_on()));
+        records.sort_by_key(|record| record.committed_at);
+        // 同一个改动在多个分支上的 cherry-pick 只保留最早的一条
+        let mut seen = HashSet::new();
+        records.retain(|record| {
+            let key = record.patch_id.as_ref().or(record.sha.as_ref());
+            key.is_none_or(|key| seen.insert(key.clone()))
+        });
+        if !records.is_empty() {
+            all_projects_commits.insert(
+                project_name,
+                records.iter().map(CommitRecord::report_text).collect(),
+            );
+        }
+    }
+    Ok(all_projects_commits)
+}
 
-    let mut project_entries = fs::read_dir(history_dir)
+async fn read_records(path: &Path) -> Result<Vec<CommitRecord>> {
+    let content = match fs::read_to_string(path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("无法读取 {}", path.display())),
+    };
+
+    let mut records = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        // 写到一半被中断的行不影响其他记录
+        match serde_json::from_str(line) {
+            Ok(record) => records.push(record),
+            Err(e) => eprintln!(
+                "⚠️  跳过 {} 第 {} 行无法解析的记录: {e}",
+                path.display(),
+                index + 1
+            ),
+        }
+    }
+    Ok(records)
+}
+
+/// 整体重写存储文件，先写入临时文件再替换，中断时不会留下写了一半的文件
+async fn write_records(project_dir: &Path, records: &[CommitRecord]) -> Result<()> {
+    let lines = to_lines(records)?;
+
+    let path = project_dir.join(STORE_FILE);
+    let temp_path = project_dir.join(format!("{STORE_FILE}.tmp"));
+    fs::write(&temp_path, lines)
         .await
-        .context("Failed to read history directory")?;
-    while let Some(project_entry) = project_entries.next_entry().await? {
-        let project_path = project_entry.path();
-        if !project_path.is_dir() {
+        .with_context(|| format!("无法写入 {}", temp_path.display()))?;
+    fs::rename(&temp_path, &path)
+        .await
+        .with_context(|| format!("无法写入 {}", path.display()))
+}
+
+fn to_lines(records: &[CommitRecord]) -> Result<String> {
+    let mut lines = String::new();
+    for record in records {
+        lines.push_str(&serde_json::to_string(record)?);
+        lines.push('\n');
+    }
+    Ok(lines)
+}
+
+async fn append_lines(project_dir: &Path, records: &[CommitRecord]) -> Result<()> {
+    if !project_dir.exists() {
+        fs::create_dir_all(project_dir)
+            .await
+            .context("Failed to create project history directory")?;
+    }
+    let lines = to_lines(records)?;
+
+    let path = project_dir.join(STORE_FILE);
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .await
+        .with_context(|| format!("无法打开 {}", path.display()))?;
+    file.write_all(lines.as_bytes())
+        .await
+        .with_context(|| format!("无法写入 {}", path.display()))
+}
+
+/// 把旧的 `<日期>.md` 转换成记录
+async fn migrate_legacy_history(project_dir: &Path, project_name: &str) -> Result<()> {
+    let Ok(mut entries) = fs::read_dir(project_dir).await else {
+        return Ok(());
+    };
+    let mut legacy_files: Vec<(NaiveDate, PathBuf)> = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if !path.is_file() || path.extension().is_none_or(|extension| extension != "md") {
             continue;
         }
+        if let Some(date) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| NaiveDate::parse_from_str(stem, "%Y-%m-%d").ok())
+        {
+            legacy_files.push((date, path));
+        }
+    }
+    if legacy_files.is_empty() {
+        return Ok(());
+    }
+    legacy_files.sort();
 
-        let project_name = project_entry.file_name().to_string_lossy().to_string();
-        let mut commits_for_project: Vec<String> = Vec::new();
-
-        if let Ok(mut day_entries) = fs::read_dir(project_path).await {
-            while let Some(day_entry) = day_entries.next_entry().await? {
-                let day_path = day_entry.path();
-                if day_path.is_file() {
-                    if let Some(filename_str) = day_path.file_stem().and_then(|s| s.to_str()) {
-                        if let Ok(date) = NaiveDate::parse_from_str(filename_str, "%Y-%m-%d") {
-                            if date >= start_date && date <= end_date {
-                                if let Ok(content) = fs::read_to_string(&day_path).await {
-                                    // Split commits by "---" and add to list
-                                    for commit in content
-                                        .split("\n\n---\n\n")
-                                        .map(|s| s.trim())
-                                        .filter(|s| !s.is_empty())
-                                    {
-                                        commits_for_project.push(commit.to_string());
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+    // 上次迁移可能在写入记录之后、移走原文件之前中断，这时已经导入的条目不能再导入一次。
+    // 同一天可能有多个提交信息相同的提交，所以按出现的次数抵消
+    let mut stored = read_records(&project_dir.join(STORE_FILE)).await?;
+    let mut already_migrated: HashMap<(NaiveDate, String), usize> = HashMap::new();
+    for record in stored.iter().filter(|record| record.sha.is_none()) {
+        *already_migrated
+            .entry((record.committed_on(), record.message.trim().to_string()))
+            .or_default() += 1;
+    }
+
+    let archived_at = Local::now().fixed_offset();
+    let mut records = Vec::new();
+    for (date, path) in &legacy_files {
+        let content = fs::read_to_string(path)
+            .await
+            .with_context(|| format!("无法读取旧的历史记录 {}", path.display()))?;
+        // 旧格式没有时间，记在当天的 00:00
+        let committed_at = date
+            .and_hms_opt(0, 0, 0)
+            .and_then(|time| time.and_local_timezone(Local).earliest())
+            .map(|time| time.fixed_offset())
+            .unwrap_or(archived_at);
+        for entry in content
+            .split(LEGACY_SEPARATOR)
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (message, notes) = match entry.split_once(LEGACY_NOTES_MARKER) {
+                Some((message, notes)) => (message, Some(notes.to_string())),
+                None => (entry, None),
+            };
+            if let Some(count) = already_migrated
+                .get_mut(&(*date, message.trim().to_string()))
+                .filter(|count| **count > 0)
+            {
+                *count -= 1;
+                continue;
             }
+            records.push(CommitRecord {
+                sha: None,
+                patch_id: None,
+                repo_path: None,
+                project: project_name.to_string(),
+                branch: None,
+                author_name: String::new(),
+                author_email: String::new(),
+                authored_at: None,
+                committed_at,
+                archived_at,
+                message: message.to_string(),
+                trailers: Trailer::parse_all(message),
+                files: Vec::new(),
+                notes,
+            });
         }
+    }
 
-        if !commits_for_project.is_empty() {
-            all_projects_commits.insert(project_name, commits_for_project);
+    let migrated = records.len();
+    stored.extend(records);
+    write_records(project_dir, &stored).await?;
+    let migrated_dir = project_dir.join(MIGRATED_DIR);
+    fs::create_dir_all(&migrated_dir)
+        .await
+        .context("Failed to create migrated history directory")?;
+    for (_, path) in &legacy_files {
+        if let Some(file_name) = path.file_name() {
+            fs::rename(path, migrated_dir.join(file_name))
+                .await
+                .with_context(|| format!("无法移动旧的历史记录 {}", path.display()))?;
         }
     }
-
-    Ok(all_projects_commits)
+    eprintln!(
+        "📦 已将 {project_name} 的 {migrated} 条旧历史记录迁移到 {STORE_FILE}，原文件保留在 {MIGRATED_DIR}/ 中"
+    );
+    Ok(())
 }
@@ -0,0 +1,127 @@
+//! src/ignore_rules.rs
+//!
+//! 读取全局 (配置目录) 和仓库级别的 `.matecode-ignore`，语法与 .gitignore 相同。
+//! 仓库级别的规则优先于全局规则。
+
+use crate::config;
+use crate::git;
+use anyhow::{Result, anyhow};
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Path, PathBuf};
+
+pub const IGNORE_FILE_NAME: &str = ".matecode-ignore";
+
+const LOCKFILE_NAMES: &[&str] = &[
+    "cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "composer.lock",
+    "gemfile.lock",
+    "poetry.lock",
+    "pipfile.lock",
+    "uv.lock",
+    "go.sum",
+];
+
+/// 一条命中的规则
+pub struct RuleMatch {
+    pub pattern: String,
+    pub source: Option<PathBuf>,
+    pub line: Option<usize>,
+    /// `!pattern` 形式的规则，表示显式保留
+    pub whitelist: bool,
+}
+
+pub struct IgnoreRules {
+    root: PathBuf,
+    /// 按优先级排列：仓库级别在前，全局在后
+    matchers: Vec<Gitignore>,
+}
+
+impl IgnoreRules {
+    /// 加载当前仓库适用的规则，规则中的路径都相对于仓库根目录
+    pub async fn load() -> Result<Self> {
+        let root = match git::get_repo_root().await {
+            Ok(toplevel) => toplevel,
+            Err(_) => std::env::current_dir()?,
+        };
+        let global_file = config::get_config_dir().await?.join(IGNORE_FILE_NAME);
+        let repo_file = root.join(IGNORE_FILE_NAME);
+
+        let mut matchers = Vec::new();
+        for file in [repo_file, global_file] {
+            if file.is_file() {
+                matchers.push(build_matcher(&root, &file)?);
+            }
+        }
+        Ok(Self { root, matchers })
+    }
+
+    /// 找出决定该路径是否被忽略的规则。路径可以是绝对路径，也可以相对于仓库根目录。
+    pub fn explain(&self, path: &Path, is_dir: bool) -> Option<RuleMatch> {
+        let path = path.strip_prefix(&self.root).unwrap_or(path);
+        // 仓库之外的路径不受这些规则约束
+        if path.is_absolute() {
+            return None;
+        }
+        self.matchers.iter().find_map(|matcher| {
+            let glob = match matcher.matched_path_or_any_parents(path, is_dir) {
+                Match::None => return None,
+                Match::Ignore(glob) | Match::Whitelist(glob) => glob,
+            };
+            Some(RuleMatch {
+                pattern: glob.original().to_string(),
+                source: glob.from().map(Path::to_path_buf),
+                line: glob
+                    .from()
+                    .and_then(|file| find_rule_line(file, glob.original())),
+                whitelist: glob.is_whitelist(),
+            })
+        })
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        self.explain(path, is_dir)
+            .is_some_and(|rule| !rule.whitelist)
+    }
+}
+
+fn build_matcher(root: &Path, file: &Path) -> Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new(root);
+    if let Some(err) = builder.add(file) {
+        return Err(anyhow!("无法读取忽略文件 {}: {}", file.display(), err));
+    }
+    builder
+        .build()
+        .map_err(|e| anyhow!("无法解析忽略文件 {}: {}", file.display(), e))
+}
+
+fn find_rule_line(file: &Path, pattern: &str) -> Option<usize> {
+    let content = std::fs::read_to_string(file).ok()?;
+    content
+        .lines()
+        .position(|line| line.trim() == pattern)
+        .map(|index| index + 1)
+}
+
+/// 是否是包管理器生成的锁文件，例如 Cargo.lock、package-lock.json
+pub fn is_lockfile(path: &str) -> bool {
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    LOCKFILE_NAMES.contains(&file_name.as_str())
+}
+
+/// 被忽略文件在 prompt 中的一行摘要，例如 "lockfile updated, +120/-80"
+pub fn ignored_file_stub(path: &str, added: usize, removed: usize) -> String {
+    let kind = if is_lockfile(path) {
+        "lockfile updated"
+    } else {
+        "ignored file changed"
+    };
+    format!("{kind}, +{added}/-{removed}")
+}
@@ -0,0 +1,114 @@
+//! src/issue.rs
+//!
+//! 从当前分支名中提取工单号 (例如 `feature/PROJ-123-login` 中的 `PROJ-123`)，
+//! 并按配置写成提交信息末尾的 trailer，例如 `Issue: PROJ-123`；也负责解析已有的 trailer。
+
+use crate::config::IssueConfig;
+use crate::git_backend;
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref TRAILER_LINE: Regex =
+        Regex::new(r"^([A-Za-z0-9][A-Za-z0-9-]*|BREAKING CHANGE): (.*)$").unwrap();
+}
+
+/// 当前分支名中的工单号，按出现顺序去重；分离 HEAD 或没有匹配时为空
+pub async fn branch_issue_keys(config: &IssueConfig) -> Result<Vec<String>> {
+    let Some(branch) = git_backend::backend().await?.current_branch().await? else {
+        return Ok(Vec::new());
+    };
+    extract_keys(config, &branch)
+}
+
+fn extract_keys(config: &IssueConfig, branch: &str) -> Result<Vec<String>> {
+    let pattern = Regex::new(&config.pattern)
+        .with_context(|| format!("issue.pattern 不是有效的正则表达式: {}", config.pattern))?;
+    let mut keys: Vec<String> = Vec::new();
+    for captures in pattern.captures_iter(branch) {
+        // 有捕获组时只取第一个捕获组，方便写出 `(\d+)-` 这样只要数字的规则
+        let key = captures
+            .get(1)
+            .or_else(|| captures.get(0))
+            .map(|key| key.as_str().to_string());
+        if let Some(key) = key
+            && !key.is_empty()
+            && !is_excluded(config, &key)
+            && !keys.contains(&key)
+        {
+            keys.push(key);
+        }
+    }
+    Ok(keys)
+}
+
+/// `exclude` 中的条目与工单号相同，或是工单号 `-` 之前的前缀 (例如 `UTF` 之于 `UTF-8`)，不区分大小写
+fn is_excluded(config: &IssueConfig, key: &str) -> bool {
+    let prefix = key.split('-').next().unwrap_or(key);
+    config
+        .exclude
+        .iter()
+        .any(|entry| entry.eq_ignore_ascii_case(key) || entry.eq_ignore_ascii_case(prefix))
+}
+
+/// 每个工单号一行 trailer，例如 `Issue: PROJ-123`
+pub fn format_trailers(config: &IssueConfig, keys: &[String]) -> Vec<String> {
+    keys.iter()
+        .map(|key| {
+            format!(
+                "{}: {}",
+                config.trailer,
+                config.format.replace("{key}", key)
+            )
+        })
+        .collect()
+}
+
+/// 把 trailer 追加到提交信息末尾。已经存在的行不会重复添加；
+/// 最后一段已经是 trailer 时直接接在后面，否则空一行
+pub fn append_trailers(message: &str, trailers: &[String]) -> String {
+    let message = message.trim_end();
+    let missing: Vec<&String> = trailers
+        .iter()
+        .filter(|trailer| !message.lines().any(|line| line.trim() == trailer.as_str()))
+        .collect();
+    if missing.is_empty() {
+        return message.to_string();
+    }
+
+    let separator = if parse_trailers(message).is_empty() {
+        "\n\n"
+    } else {
+        "\n"
+    };
+
+    let mut result = message.to_string();
+    result.push_str(separator);
+    result.push_str(
+        &missing
+            .iter()
+            .map(|trailer| trailer.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    );
+    result
+}
+
+/// 提交信息末尾的 trailer，例如 `("Issue", "PROJ-123")`；
+/// 只有标题之后的最后一段全部是 `Key: value` 形式时才算
+pub fn parse_trailers(message: &str) -> Vec<(String, String)> {
+    let message = message.trim_end();
+    let Some((_, last_paragraph)) = message.rsplit_once("\n\n") else {
+        return Vec::new();
+    };
+    let trailers: Option<Vec<(String, String)>> = last_paragraph
+        .lines()
+        .map(|line| {
+            TRAILER_LINE
+                .captures(line)
+                .map(|captures| (captures[1].to_string(), captures[2].trim().to_string()))
+        })
+        .collect();
+    trailers.unwrap_or_default()
+}
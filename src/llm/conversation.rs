@@ -0,0 +1,245 @@
+//! src/llm/conversation.rs
+use crate::git::estimeate_token_count;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub role: Role,
+    pub content: String,
+}
+
+impl Message {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self {
+            role: Role::System,
+            content: content.into(),
+        }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: Role::User,
+            content: content.into(),
+        }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self {
+            role: Role::Assistant,
+            content: content.into(),
+        }
+    }
+}
+
+/// 把多轮消息压平成 (system, user) 两段，给不支持多轮对话的 provider 使用。
+pub fn flatten_messages(messages: &[Message]) -> (String, String) {
+    let system_prompt = messages
+        .iter()
+        .filter(|m| m.role == Role::System)
+        .map(|m| m.content.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let turns: Vec<&Message> = messages.iter().filter(|m| m.role != Role::System).collect();
+    let user_prompt = if let [only] = turns.as_slice() {
+        only.content.clone()
+    } else {
+        turns
+            .iter()
+            .map(|m| format!("<{}>\n{}\n</{}>", m.role.as_str(), m.content, m.role.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    };
+
+    (system_prompt, user_prompt)
+}
+
+/// 一段持续的对话。开头的 system 和第一条 user 消息（通常是 diff）作为固定上下文，
+/// 之后每一轮的反馈和回答都会追加进来。
+#[derive(Debug, Clone)]
+pub struct Conversation {
+    messages: Vec<Message>,
+    /// 固定上下文的消息条数，截断时不会被丢弃
+    pinned: usize,
+}
+
+impl Conversation {
+    pub fn new(system_prompt: impl Into<String>, context: impl Into<String>) -> Self {
+        Self {
+            messages: vec![Message::system(system_prompt), Message::user(context)],
+            pinned: 2,
+        }
+    }
+
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// 当前位置，配合 [`Conversation::truncate`] 使用。
+    pub fn checkpoint(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn push(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+
+    /// 回退到之前的某个长度，用于放弃一次改进或撤销失败的请求。
+    pub fn truncate(&mut self, len: usize) {
+        self.messages.truncate(len.max(self.pinned));
+    }
+
+    /// 当历史超过 token 预算时，丢弃最早的几轮对话，
+    /// 并把被丢弃的用户反馈压缩成一条备注，保证之前的要求不会被遗忘。
+    pub fn fit_to_budget(&mut self, token_budget: usize) {
+        if self.total_tokens() <= token_budget {
+            return;
+        }
+
+        let mut dropped_feedback: Vec<String> = Vec::new();
+        // 至少保留最新的一轮 (user + assistant)
+        while self.messages.len() > self.pinned + 2 && self.total_tokens() > token_budget {
+            let removed = self.messages.remove(self.pinned);
+            if removed.role != Role::User {
+                continue;
+            }
+            // 和对应的回答一起丢弃，避免留下没有提问的回答
+            if self.messages[self.pinned].role == Role::Assistant {
+                self.messages.remove(self.pinned);
+            }
+            match removed.content.strip_prefix(EARLIER_FEEDBACK_HEADER) {
+                Some(items) => dropped_feedback.extend(
+                    items
+                        .lines()
+                        .filter_map(|line| line.strip_prefix("- "))
+                        .map(String::from),
+                ),
+                None => dropped_feedback
+                    .push(removed.content.lines().next().unwrap_or_default().to_string()),
+            }
+        }
+
+        if !dropped_feedback.is_empty() {
+            let note = format!(
+                "{}\n{}",
+                EARLIER_FEEDBACK_HEADER,
+                dropped_feedback
+                    .iter()
+                    .map(|feedback| format!("- {feedback}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+            self.messages.insert(self.pinned, Message::user(note));
+            self.messages.insert(self.pinned + 1, Message::assistant("好的，我会继续遵循这些要求。"));
+        }
+
+        // 仍然超出预算时，只能截断固定上下文（diff）本身。
+        // token 按字节数估算，所以按字节截断，再退到最近的字符边界
+        let overflow = self.total_tokens().saturating_sub(token_budget);
+        if overflow > 0 {
+            let context = &mut self.messages[self.pinned - 1].content;
+            let mut keep_bytes = context
+                .len()
+                .saturating_sub(overflow * 3 + TRUNCATED_MARKER.len());
+            while !context.is_char_boundary(keep_bytes) {
+                keep_bytes -= 1;
+            }
+            context.truncate(keep_bytes);
+            context.push_str(TRUNCATED_MARKER);
+        }
+    }
+
+    fn total_tokens(&self) -> usize {
+        self.messages
+            .iter()
+            .map(|m| estimeate_token_count(&m.content))
+            .sum()
+    }
+}
+
+const EARLIER_FEEDBACK_HEADER: &str = "此前几轮对话中我提出过的改进要求：";
+const TRUNCATED_MARKER: &str = "\n... (content truncated)";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn refined_conversation(context: &str, rounds: usize) -> Conversation {
+        let mut conversation = Conversation::new("system", context);
+        for round in 0..rounds {
+            let details = "detail ".repeat(50);
+            conversation.push(Message::user(format!("feedback {round}\n{details}")));
+            conversation.push(Message::assistant(format!("answer {round}")));
+        }
+        conversation
+    }
+
+    #[test]
+    fn fit_to_budget_drops_earliest_rounds_first() {
+        let mut conversation = refined_conversation("diff", 3);
+        let full = conversation.total_tokens();
+        conversation.fit_to_budget(full);
+        assert_eq!(conversation.messages().len(), 8);
+
+        conversation.fit_to_budget(full - 50);
+        assert!(conversation.total_tokens() <= full - 50);
+        let contents: Vec<&str> = conversation
+            .messages()
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect();
+        // 最早的一轮被压缩成备注，之后的几轮和 diff 保持不变
+        assert_eq!(contents.len(), 8);
+        assert_eq!(contents[1], "diff");
+        assert_eq!(contents[2], format!("{EARLIER_FEEDBACK_HEADER}\n- feedback 0"));
+        assert!(contents[4].starts_with("feedback 1"));
+        assert_eq!(contents[7], "answer 2");
+    }
+
+    #[test]
+    fn fit_to_budget_truncates_context_by_bytes() {
+        let context = "a".repeat(3000);
+        let mut conversation = refined_conversation(&context, 1);
+        let budget = conversation.total_tokens() - 100;
+        conversation.fit_to_budget(budget);
+
+        assert!(conversation.total_tokens() <= budget);
+        let truncated = &conversation.messages()[1].content;
+        assert!(truncated.ends_with(TRUNCATED_MARKER));
+        assert_eq!(truncated.len(), 3000 - 300);
+    }
+
+    #[test]
+    fn fit_to_budget_truncates_cjk_context_on_char_boundary() {
+        // 每个汉字占 3 个字节，估算为 1 个 token
+        let context = "改".repeat(1000);
+        let mut conversation = refined_conversation(&context, 1);
+        let budget = conversation.total_tokens() - 100;
+        conversation.fit_to_budget(budget);
+
+        assert!(conversation.total_tokens() <= budget);
+        let truncated = &conversation.messages()[1].content;
+        let kept = truncated.strip_suffix(TRUNCATED_MARKER).unwrap();
+        // 超出 100 个 token，只应丢掉大约 100 个汉字，而不是 300 个
+        let kept_chars = kept.chars().count();
+        assert!(kept_chars >= 1000 - 100 - TRUNCATED_MARKER.len());
+        assert!(kept_chars < 1000 - 100);
+        assert!(kept.chars().all(|c| c == '改'));
+    }
+}
@@ -0,0 +1,244 @@
+//! src/llm/exec.rs
+//!
+//! 外部命令 provider：启动配置的命令，通过 stdin 发送
+//! `{"system", "user", "model", "params"}`，并从 stdout 读取
+//! `{"text", "usage"}`。stderr 直接透传给终端，`usage` 为可选字段，
+//! 格式为 `{"prompt_tokens", "completion_tokens", "total_tokens"}`。
+use super::{LLMClient, print_status};
+use crate::config::{ExecProvider, ModelConfig};
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+
+/// 命令失败时在错误信息中附带的 stderr 行数
+const STDERR_TAIL_LINES: usize = 20;
+
+#[derive(Serialize)]
+struct ExecRequest<'a> {
+    system: &'a str,
+    user: &'a str,
+    model: &'a str,
+    params: &'a HashMap<String, serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct ExecResponse {
+    text: String,
+    #[serde(default)]
+    usage: Option<ExecUsage>,
+}
+
+#[derive(Deserialize)]
+struct ExecUsage {
+    prompt_tokens: Option<u64>,
+    completion_tokens: Option<u64>,
+    total_tokens: Option<u64>,
+}
+
+impl ExecUsage {
+    fn report(&self) -> Option<String> {
+        let parts: Vec<String> = [
+            ("输入", self.prompt_tokens),
+            ("输出", self.completion_tokens),
+            ("合计", self.total_tokens),
+        ]
+        .into_iter()
+        .filter_map(|(label, tokens)| tokens.map(|tokens| format!("{label} {tokens}")))
+        .collect();
+        (!parts.is_empty()).then(|| format!("📊 外部 LLM 命令 token 用量: {}", parts.join(", ")))
+    }
+}
+
+pub struct ExecClient {
+    command: String,
+    args: Vec<String>,
+    model_name: String,
+    params: HashMap<String, serde_json::Value>,
+    timeout: Duration,
+    model_config: ModelConfig,
+    task_timeouts: HashMap<String, u64>,
+}
+
+impl ExecClient {
+    pub fn new(config: &ExecProvider) -> Result<Self> {
+        let model_name = config.default_model.clone();
+
+        let model_config = config.models.get(&model_name)
+            .or_else(|| config.models.get("default"))
+            .ok_or_else(|| anyhow!("Configuration for model '{}' not found, and no default configuration available.", model_name))?
+            .clone();
+
+        Ok(Self {
+            command: config.command.clone(),
+            args: config.args.clone(),
+            model_name,
+            params: config.params.clone(),
+            timeout: Duration::from_secs(config.timeout_secs),
+            model_config,
+            task_timeouts: config.task_timeouts.clone(),
+        })
+    }
+
+    async fn run_command(&self, payload: &[u8]) -> Result<Vec<u8>> {
+        let mut child = Command::new(&self.command)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("无法启动外部 LLM 命令: {}", self.command))?;
+
+        let stderr = child
+            .stderr
+            .take()
+            .context("无法获取外部 LLM 命令的 stderr")?;
+        let stderr_tail = tokio::spawn(forward_stderr(stderr));
+
+        let mut stdin = child
+            .stdin
+            .take()
+            .context("无法获取外部 LLM 命令的 stdin")?;
+        // 插件可能不读完请求就退出，写入失败时仍然等待它结束，以便报告退出状态和 stderr
+        let written = stdin.write_all(payload).await;
+        // 关闭 stdin，让插件知道请求已经发送完毕
+        drop(stdin);
+
+        let output = child
+            .wait_with_output()
+            .await
+            .context("等待外部 LLM 命令结束失败")?;
+        let stderr_tail = stderr_tail.await.unwrap_or_default();
+
+        match written {
+            Ok(()) if output.status.success() => Ok(output.stdout),
+            Ok(()) => Err(anyhow!(
+                "外部 LLM 命令执行失败, status: {}{}",
+                output.status,
+                format_stderr(&stderr_tail)
+            )),
+            Err(e) => Err(anyhow!(
+                "向外部 LLM 命令写入请求失败: {}, status: {}{}",
+                e,
+                output.status,
+                format_stderr(&stderr_tail)
+            )),
+        }
+    }
+}
+
+/// 把插件的 stderr 逐行透传到终端 (不打乱进度条)，并保留最后几行用于错误信息
+async fn forward_stderr(stderr: impl AsyncRead + Unpin) -> String {
+    let mut reader = BufReader::new(stderr);
+    let mut tail = VecDeque::new();
+    let mut line = Vec::new();
+    while let Ok(read) = reader.read_until(b'\n', &mut line).await
+        && read > 0
+    {
+        let text = String::from_utf8_lossy(&line).trim_end().to_string();
+        print_status(&text);
+        if tail.len() == STDERR_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(text);
+        line.clear();
+    }
+    Vec::from(tail).join("\n")
+}
+
+fn format_stderr(stderr: &str) -> String {
+    if stderr.trim().is_empty() {
+        String::new()
+    } else {
+        format!("\nstderr:\n{stderr}")
+    }
+}
+
+#[async_trait]
+impl LLMClient for ExecClient {
+    fn model_config(&self) -> &ModelConfig {
+        &self.model_config
+    }
+
+    /// 命令只受一个超时限制：任务单独配置的超时优先，否则使用 `timeout_secs`
+    fn task_timeout(&self, task: &str) -> Option<Duration> {
+        let timeout = self
+            .task_timeouts
+            .get(task)
+            .copied()
+            .map_or(self.timeout, Duration::from_secs);
+        Some(timeout)
+    }
+
+    async fn call(&self, system_prompt: &str, user_prompt: &str) -> Result<String> {
+        let request = ExecRequest {
+            system: system_prompt,
+            user: user_prompt,
+            model: &self.model_name,
+            params: &self.params,
+        };
+        let payload = serde_json::to_vec(&request)?;
+
+        // 超时由 `call_task` / `chat_task` 按 `task_timeout` 统一施加，超时后进程随 future 一起被终止
+        let stdout = self.run_command(&payload).await?;
+
+        let response: ExecResponse = serde_json::from_slice(&stdout).map_err(|e| {
+            anyhow!(
+                "解析外部 LLM 命令输出失败: {}\n输出内容: {}",
+                e,
+                String::from_utf8_lossy(&stdout)
+            )
+        })?;
+
+        if let Some(report) = response.usage.as_ref().and_then(ExecUsage::report) {
+            print_status(&report);
+        }
+
+        let text = response.text.trim();
+        if text.is_empty() {
+            Err(anyhow!("外部 LLM 命令返回了空响应"))
+        } else {
+            Ok(text.to_string())
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::llm::chat_task;
+    use crate::llm::conversation::Message;
+
+    fn client(task_timeouts: &str) -> ExecClient {
+        let config: ExecProvider = toml::from_str(&format!(
+            r#"
+            command = "sh"
+            args = ["-c", "cat > /dev/null; sleep 2; echo '{{\"text\": \"done\"}}'"]
+            default_model = "in-house"
+            timeout_secs = 1
+            task_timeouts = {{ {task_timeouts} }}
+            models = {{ "default" = {{ max_tokens = 4096, max_output_tokens = 1024, reserved_tokens = 500 }} }}
+            "#
+        ))
+        .unwrap();
+        ExecClient::new(&config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn task_timeout_overrides_timeout_secs() {
+        let messages = [Message::user("refine this")];
+
+        let response = chat_task(&client("refine = 5"), "refine", &messages).await;
+        assert_eq!(response.unwrap(), "done");
+
+        let error = chat_task(&client("refine = 5"), "commit", &messages)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("超时"), "{error}");
+    }
+}
@@ -1,10 +1,14 @@
 //! src/llm/gemini.rs
 use super::LLMClient;
+use super::conversation::{Message, Role};
+use super::http::build_http_client;
 use crate::config::{GeminiProvider, ModelConfig};
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
 
 #[derive(Serialize)]
 struct GeminiRequest<'a> {
@@ -13,12 +17,13 @@ struct GeminiRequest<'a> {
 
 #[derive(Serialize)]
 struct Content<'a> {
-    parts: Vec<Part<'a>>,
+    role: &'a str,
+    parts: Vec<Part>,
 }
 
 #[derive(Serialize)]
-struct Part<'a> {
-    text: &'a str,
+struct Part {
+    text: String,
 }
 
 #[derive(Deserialize, Debug)]
@@ -41,13 +46,12 @@ struct PartResponse {
     text: Option<String>,
 }
 
-const FAKE_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36";
-
 pub struct GeminiClient {
     api_key: String,
     model_name: String,
     client: Client,
     model_config: ModelConfig,
+    task_timeouts: HashMap<String, u64>,
 }
 
 impl GeminiClient {
@@ -60,21 +64,14 @@ impl GeminiClient {
             .ok_or_else(|| anyhow!("Configuration for model '{}' not found, and no default configuration available.", model_name))?
             .clone();
 
-        let mut client_builder = Client::builder().user_agent(FAKE_USER_AGENT);
-
-        if let Some(proxy_url) = &config.proxy {
-            let proxy = reqwest::Proxy::all(proxy_url)
-                .map_err(|e| anyhow!("Failed to create proxy: {}", e))?;
-            client_builder = client_builder.proxy(proxy);
-        }
-
-        let client = client_builder.build()?;
+        let client = build_http_client(&config.http)?;
 
         Ok(Self {
             api_key,
             model_name,
             client,
             model_config,
+            task_timeouts: config.http.task_timeouts.clone(),
         })
     }
 }
@@ -85,24 +82,48 @@ impl LLMClient for GeminiClient {
         &self.model_config
     }
 
-    async fn call(&self, _system_prompt: &str, user_prompt: &str) -> Result<String> {
-        // Gemini API does not have a separate system prompt, so we prepend it to the user prompt.
-        let full_prompt = if !_system_prompt.is_empty() {
-            format!("{_system_prompt}\n\n{user_prompt}")
-        } else {
-            user_prompt.to_string()
-        };
+    fn task_timeout(&self, task: &str) -> Option<Duration> {
+        self.task_timeouts.get(task).copied().map(Duration::from_secs)
+    }
+
+    async fn call(&self, system_prompt: &str, user_prompt: &str) -> Result<String> {
+        self.chat(&[Message::system(system_prompt), Message::user(user_prompt)])
+            .await
+    }
+
+    async fn chat(&self, messages: &[Message]) -> Result<String> {
+        // Gemini API does not have a separate system prompt, so we prepend it to the first user turn.
+        let system_prompt = messages
+            .iter()
+            .filter(|m| m.role == Role::System && !m.content.is_empty())
+            .map(|m| m.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let mut contents: Vec<Content> = Vec::new();
+        for message in messages.iter().filter(|m| m.role != Role::System) {
+            let role = if message.role == Role::Assistant {
+                "model"
+            } else {
+                "user"
+            };
+            let text = if contents.is_empty() && !system_prompt.is_empty() {
+                format!("{system_prompt}\n\n{}", message.content)
+            } else {
+                message.content.clone()
+            };
+            contents.push(Content {
+                role,
+                parts: vec![Part { text }],
+            });
+        }
 
         let api_url = format!(
             "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
             self.model_name, self.api_key
         );
 
-        let request_payload = GeminiRequest {
-            contents: vec![Content {
-                parts: vec![Part { text: &full_prompt }],
-            }],
-        };
+        let request_payload = GeminiRequest { contents };
 
         let res = self
             .client
@@ -110,7 +131,13 @@ impl LLMClient for GeminiClient {
             .json(&request_payload)
             .send()
             .await
-            .map_err(|e| anyhow!("Failed to send request to Gemini API: {}", e))?;
+            .map_err(|e| {
+                if e.is_timeout() {
+                    anyhow!("Gemini API request timed out: {}", e)
+                } else {
+                    anyhow!("Failed to send request to Gemini API: {}", e)
+                }
+            })?;
 
         let res_status = res.status();
 
@@ -0,0 +1,101 @@
+//! src/llm/http.rs
+use crate::config::HttpConfig;
+use anyhow::{Context, Result, anyhow};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Certificate, Client, NoProxy, Proxy};
+use std::time::Duration;
+
+const DEFAULT_USER_AGENT: &str = concat!("matecode/", env!("CARGO_PKG_VERSION"));
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_READ_TIMEOUT_SECS: u64 = 120;
+
+/// 根据 provider 的 HTTP 配置构建 reqwest 客户端，所有 LLM 客户端都通过这里创建。
+pub fn build_http_client(config: &HttpConfig) -> Result<Client> {
+    let user_agent = config.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT);
+    let mut client_builder = Client::builder()
+        .user_agent(user_agent)
+        .default_headers(build_headers(config)?)
+        .connect_timeout(Duration::from_secs(
+            config
+                .connect_timeout_secs
+                .unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS),
+        ))
+        .read_timeout(Duration::from_secs(
+            config.read_timeout_secs.unwrap_or(DEFAULT_READ_TIMEOUT_SECS),
+        ));
+
+    if let Some(proxy_url) = &config.proxy {
+        let proxy =
+            Proxy::all(proxy_url).map_err(|e| anyhow!("Failed to create proxy: {}", e))?;
+        let no_proxy = config.no_proxy.as_deref().and_then(NoProxy::from_string);
+        client_builder = client_builder.proxy(proxy.no_proxy(no_proxy));
+    } else if let Some(no_proxy) = &config.no_proxy {
+        for proxy in env_proxies(no_proxy)? {
+            client_builder = client_builder.proxy(proxy);
+        }
+    }
+
+    if let Some(ca_cert_path) = &config.ca_cert_path {
+        client_builder = client_builder.add_root_certificate(load_certificate(ca_cert_path)?);
+    }
+
+    if config.danger_accept_invalid_certs {
+        eprintln!("⚠️  已关闭 TLS 证书校验 (danger_accept_invalid_certs = true)，请勿在生产环境使用。");
+        client_builder = client_builder.danger_accept_invalid_certs(true);
+    }
+
+    client_builder
+        .build()
+        .map_err(|e| anyhow!("Failed to build HTTP client: {}", e))
+}
+
+/// 只设置了 `no_proxy` 时作用于环境变量中的代理。手动添加代理后 reqwest 不再读取
+/// 代理相关的环境变量，所以这里按 `HTTP_PROXY`、`HTTPS_PROXY`、`ALL_PROXY` 重新创建，
+/// 并把 `no_proxy` 和 `NO_PROXY` 环境变量合并
+fn env_proxies(no_proxy: &str) -> Result<Vec<Proxy>> {
+    let env = |name: &str| {
+        std::env::var(name)
+            .or_else(|_| std::env::var(name.to_lowercase()))
+            .ok()
+            .filter(|value| !value.is_empty())
+    };
+    let no_proxy = match env("NO_PROXY") {
+        Some(env_no_proxy) => format!("{no_proxy},{env_no_proxy}"),
+        None => no_proxy.to_string(),
+    };
+
+    let mut proxies = Vec::new();
+    for name in ["HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY"] {
+        let Some(url) = env(name) else {
+            continue;
+        };
+        let proxy = match name {
+            "HTTP_PROXY" => Proxy::http(&url),
+            "HTTPS_PROXY" => Proxy::https(&url),
+            _ => Proxy::all(&url),
+        }
+        .map_err(|e| anyhow!("Failed to create proxy from {}: {}", name, e))?;
+        proxies.push(proxy.no_proxy(NoProxy::from_string(&no_proxy)));
+    }
+    Ok(proxies)
+}
+
+fn build_headers(config: &HttpConfig) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &config.extra_headers {
+        let header_name = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("无效的 HTTP header 名称: {name}"))?;
+        let header_value = HeaderValue::from_str(value)
+            .with_context(|| format!("HTTP header '{name}' 的值无效"))?;
+        headers.insert(header_name, header_value);
+    }
+    Ok(headers)
+}
+
+fn load_certificate(path: &str) -> Result<Certificate> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("无法读取 CA 证书文件: {path}"))?;
+    Certificate::from_pem(&bytes)
+        .or_else(|_| Certificate::from_der(&bytes))
+        .map_err(|e| anyhow!("无法解析 CA 证书 {}: {}", path, e))
+}
@@ -0,0 +1,39 @@
+//! src/llm/limiter.rs
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::{Instant, sleep_until};
+
+/// 简单的请求节流器：按 `requests_per_minute` 把请求均匀地分布到时间轴上。
+pub struct RateLimiter {
+    interval: Option<Duration>,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: Option<u32>) -> Self {
+        let interval = requests_per_minute
+            .filter(|rpm| *rpm > 0)
+            .map(|rpm| Duration::from_secs(60) / rpm);
+
+        Self {
+            interval,
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// 等待直到可以发出下一个请求。
+    pub async fn acquire(&self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + interval;
+            slot
+        };
+
+        sleep_until(slot).await;
+    }
+}
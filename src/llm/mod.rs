@@ -1,25 +1,50 @@
 //! src/llm/mod.rs
 
-use crate::config::{Config, ModelConfig, get_prompt_template};
-use crate::git::{DiffAnalysis, DiffChunk, ProjectContext};
+use crate::config::{Config, ContextConfig, ModelConfig, get_prompt_template};
+use crate::git::{DiffAnalysis, DiffChunk, ProjectContext, estimeate_token_count};
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
+use colored::Colorize;
+use conversation::{Message, flatten_messages};
 use futures::stream::{self, StreamExt};
-use indicatif::{ProgressBar, ProgressStyle};
+use guard::Fence;
+use indicatif::{ProgressBar, ProgressStyle, WeakProgressBar};
+use limiter::RateLimiter;
+use std::sync::Mutex;
 use std::time::Duration;
 
+pub mod conversation;
+pub mod exec;
 pub mod gemini;
+pub mod guard;
+pub mod http;
+pub mod limiter;
 pub mod openai;
+pub mod secrets;
 
 #[async_trait]
 pub trait LLMClient: Send + Sync {
     fn model_config(&self) -> &ModelConfig;
     async fn call(&self, system_prompt: &str, user_prompt: &str) -> Result<String>;
+
+    /// 某个任务 (commit, summarize, combine, report, understand, refine) 的整体超时时间。
+    fn task_timeout(&self, _task: &str) -> Option<Duration> {
+        None
+    }
+
+    /// 多轮对话调用。默认实现把对话压平后交给 [`LLMClient::call`]，
+    /// 原生支持多轮消息的 provider 应该覆盖这个方法。
+    async fn chat(&self, messages: &[Message]) -> Result<String> {
+        let (system_prompt, user_prompt) = flatten_messages(messages);
+        self.call(&system_prompt, &user_prompt).await
+    }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 pub enum LLM {
     OpenAI(openai::OpenAIClient),
     Gemini(gemini::GeminiClient),
+    Exec(exec::ExecClient),
 }
 
 impl LLM {
@@ -27,6 +52,7 @@ impl LLM {
         match self {
             LLM::OpenAI(client) => client,
             LLM::Gemini(client) => client,
+            LLM::Exec(client) => client,
         }
     }
 }
@@ -49,11 +75,74 @@ pub fn create_llm_client(config: &Config) -> Result<LLM> {
                 .ok_or_else(|| anyhow!("Gemini 配置未找到"))?;
             Ok(LLM::Gemini(gemini::GeminiClient::new(gemini_config)?))
         }
+        "exec" => {
+            let exec_config = config
+                .llm
+                .exec
+                .as_ref()
+                .ok_or_else(|| anyhow!("exec 配置未找到"))?;
+            Ok(LLM::Exec(exec::ExecClient::new(exec_config)?))
+        }
         _ => Err(anyhow!("不支持的 LLM 提供商: {}", config.provider)),
     }
 }
 
-pub async fn generate_commit_message(client: &dyn LLMClient, diff: &str) -> Result<String> {
+/// 正在显示的进度条，provider 在调用过程中输出的提示通过它打印
+static ACTIVE_PROGRESS: Mutex<Option<WeakProgressBar>> = Mutex::new(None);
+
+/// 登记正在显示的进度条，之后 [`print_status`] 会在它上方输出
+pub fn track_progress(progress_bar: &ProgressBar) {
+    let mut active = ACTIVE_PROGRESS.lock().unwrap_or_else(|e| e.into_inner());
+    *active = Some(progress_bar.downgrade());
+}
+
+/// 向 stderr 输出一行提示。进度条还在显示时先暂停它，避免把进度条打乱
+pub fn print_status(line: &str) {
+    let progress_bar = ACTIVE_PROGRESS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .and_then(WeakProgressBar::upgrade)
+        .filter(|progress_bar| !progress_bar.is_finished());
+    match progress_bar {
+        Some(progress_bar) => progress_bar.suspend(|| eprintln!("{line}")),
+        None => eprintln!("{line}"),
+    }
+}
+
+/// 调用 LLM，并应用该任务配置的超时时间。
+pub async fn call_task(
+    client: &dyn LLMClient,
+    task: &str,
+    system_prompt: &str,
+    user_prompt: &str,
+) -> Result<String> {
+    with_task_timeout(client, task, client.call(system_prompt, user_prompt)).await
+}
+
+/// 多轮对话版本的 [`call_task`]。
+pub async fn chat_task(client: &dyn LLMClient, task: &str, messages: &[Message]) -> Result<String> {
+    with_task_timeout(client, task, client.chat(messages)).await
+}
+
+async fn with_task_timeout(
+    client: &dyn LLMClient,
+    task: &str,
+    request: impl Future<Output = Result<String>>,
+) -> Result<String> {
+    match client.task_timeout(task) {
+        Some(limit) => tokio::time::timeout(limit, request)
+            .await
+            .map_err(|_| anyhow!("LLM 任务 '{}' 超时 ({}秒)", task, limit.as_secs()))?,
+        None => request.await,
+    }
+}
+
+pub async fn generate_commit_message(
+    client: &dyn LLMClient,
+    diff: &str,
+    context: &ContextConfig,
+) -> Result<String> {
     let progress_bar = ProgressBar::new_spinner();
     progress_bar.set_style(
         ProgressStyle::with_template("{spinner:.green} {msg}")
@@ -61,18 +150,31 @@ pub async fn generate_commit_message(client: &dyn LLMClient, diff: &str) -> Resu
             .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏"),
     );
     progress_bar.enable_steady_tick(Duration::from_millis(100));
+    track_progress(&progress_bar);
     progress_bar.set_message("Analyzing changes...");
 
-    let analysis = crate::git::analyze_diff(diff, client.model_config()).await?;
+    progress_bar.suspend(|| guard::warn_if_suspicious("暂存的 diff", diff));
+
+    let analysis = crate::git::analyze_diff(diff, client.model_config(), context).await?;
+    // 每次生成使用新的随机分隔符包裹 diff 和摘要
+    let fence = Fence::new();
 
     let commit_message = if analysis.needs_chunking {
-        generate_chunked_commit_message(client, &analysis, &progress_bar).await?
+        generate_chunked_commit_message(client, &analysis, &progress_bar, &fence).await?
     } else {
         progress_bar.set_message("Generating commit message...");
-        generate_single_chunk_commit_message(client, &analysis).await?
+        generate_single_chunk_commit_message(client, &analysis, &fence).await?
     };
 
     progress_bar.finish_with_message("✓ Commit message generated.");
+
+    if !guard::references_affected_files(&commit_message, &analysis.context.affected_files) {
+        eprintln!(
+            "{}",
+            "⚠️  生成的提交信息没有提到任何本次修改的文件或模块，可能与实际变更不符，请仔细核对。"
+                .yellow()
+        );
+    }
     Ok(commit_message)
 }
 
@@ -80,6 +182,24 @@ async fn generate_chunked_commit_message(
     client: &dyn LLMClient,
     analysis: &DiffAnalysis,
     progress_bar: &ProgressBar,
+    fence: &Fence,
+) -> Result<String> {
+    let summaries =
+        summarize_changes(client, client.model_config(), analysis, progress_bar, fence).await?;
+
+    progress_bar.set_style(ProgressStyle::with_template("{spinner:.green} {msg}").unwrap());
+    progress_bar.set_message("Combining summaries...");
+
+    combine_summaries(client, &analysis.context, &summaries, fence).await
+}
+
+/// 分块总结整个 diff，摘要过长时继续合并，返回能放进 `model_config` 上下文窗口的摘要文本。
+async fn summarize_changes(
+    client: &dyn LLMClient,
+    model_config: &ModelConfig,
+    analysis: &DiffAnalysis,
+    progress_bar: &ProgressBar,
+    fence: &Fence,
 ) -> Result<String> {
     progress_bar.set_style(
         ProgressStyle::default_bar()
@@ -91,53 +211,351 @@ async fn generate_chunked_commit_message(
     progress_bar.set_position(0);
     progress_bar.set_message("Summarizing chunks...");
 
-    let summaries_stream = stream::iter(
-        analysis
-            .chunks
-            .iter()
-            .map(|chunk| async move { summarize_chunk(client, &analysis.context, chunk).await }),
-    );
+    let limiter = RateLimiter::new(model_config.requests_per_minute);
+
+    // Map: 按原始顺序总结每个块，保证文件顺序不被打乱
+    let summaries = summarize_in_order(
+        client,
+        &analysis.context,
+        &analysis.chunks,
+        &limiter,
+        progress_bar,
+        fence,
+    )
+    .await?;
+
+    let labeled_summaries = analysis
+        .chunks
+        .iter()
+        .zip(summaries)
+        .map(|(chunk, text)| LabeledSummary {
+            files: chunk.files.clone(),
+            text,
+        })
+        .collect();
+
+    // Reduce: 摘要过长时递归分组再总结，直到能放进上下文窗口
+    let budget = summaries_token_budget(model_config, &analysis.context);
+    reduce_summaries(
+        client,
+        &analysis.context,
+        labeled_summaries,
+        budget,
+        &limiter,
+        progress_bar,
+        fence,
+    )
+    .await
+}
+
+/// 一个带有来源文件标签的块摘要。
+struct LabeledSummary {
+    files: Vec<String>,
+    text: String,
+}
 
-    // Process chunks concurrently.
-    let mut summaries = Vec::with_capacity(analysis.chunks.len());
-    let mut buffered_stream = summaries_stream.buffer_unordered(4); // Concurrency level: 4
+fn render_summaries(summaries: &[LabeledSummary]) -> String {
+    let total = summaries.len();
+    summaries
+        .iter()
+        .enumerate()
+        .map(|(index, summary)| {
+            format!(
+                "[{}/{}] {}\n{}",
+                index + 1,
+                total,
+                summary.files.join(", "),
+                summary.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// combine 提示词中留给摘要的 token 数量
+fn summaries_token_budget(model_config: &ModelConfig, context: &ProjectContext) -> usize {
+    model_config
+        .max_tokens
+        .saturating_sub(model_config.reserved_tokens)
+        .saturating_sub(context.prompt_tokens())
+        .max(1)
+}
 
-    while let Some(result) = buffered_stream.next().await {
+/// 并发总结多个块，但结果严格按照输入顺序返回。
+async fn summarize_in_order(
+    client: &dyn LLMClient,
+    context: &ProjectContext,
+    chunks: &[DiffChunk],
+    limiter: &RateLimiter,
+    progress_bar: &ProgressBar,
+    fence: &Fence,
+) -> Result<Vec<String>> {
+    let concurrency = client.model_config().max_concurrency.max(1);
+    let mut ordered_stream = stream::iter(chunks.iter().map(|chunk| async move {
+        limiter.acquire().await;
+        summarize_chunk(client, context, chunk, fence).await
+    }))
+    .buffered(concurrency);
+
+    let mut summaries = Vec::with_capacity(chunks.len());
+    while let Some(result) = ordered_stream.next().await {
         summaries.push(result?);
         progress_bar.inc(1);
     }
+    Ok(summaries)
+}
 
-    progress_bar.set_style(ProgressStyle::with_template("{spinner:.green} {msg}").unwrap());
-    progress_bar.set_message("Combining summaries...");
+async fn reduce_summaries(
+    client: &dyn LLMClient,
+    context: &ProjectContext,
+    mut summaries: Vec<LabeledSummary>,
+    budget: usize,
+    limiter: &RateLimiter,
+    progress_bar: &ProgressBar,
+    fence: &Fence,
+) -> Result<String> {
+    let mut level = 1;
+    loop {
+        let rendered = render_summaries(&summaries);
+        if summaries.len() <= 1 || estimeate_token_count(&rendered) <= budget {
+            return Ok(rendered);
+        }
+
+        let groups = group_summaries(summaries, (budget * 3) / 4);
+        let group_chunks: Vec<DiffChunk> = groups
+            .iter()
+            .map(|group| {
+                let mut files: Vec<String> = Vec::new();
+                for summary in group {
+                    for file in &summary.files {
+                        if !files.contains(file) {
+                            files.push(file.clone());
+                        }
+                    }
+                }
+                DiffChunk::new(files, render_summaries(group))
+            })
+            .collect();
+
+        progress_bar.set_length(group_chunks.len() as u64);
+        progress_bar.set_position(0);
+        progress_bar.set_message(format!("Reducing summaries (level {level})..."));
+
+        let reduced =
+            summarize_in_order(client, context, &group_chunks, limiter, progress_bar, fence)
+                .await?;
+        summaries = group_chunks
+            .into_iter()
+            .zip(reduced)
+            .map(|(chunk, text)| LabeledSummary {
+                files: chunk.files,
+                text,
+            })
+            .collect();
+        level += 1;
+    }
+}
+
+/// 按顺序把相邻摘要打包到不超过 `token_limit` 的分组中。
+/// 每组至少包含两条摘要（如果还有剩余），以保证每一轮都能减少摘要数量。
+fn group_summaries(summaries: Vec<LabeledSummary>, token_limit: usize) -> Vec<Vec<LabeledSummary>> {
+    let mut groups = Vec::new();
+    let mut current: Vec<LabeledSummary> = Vec::new();
+    let mut current_tokens = 0;
+
+    for summary in summaries {
+        let tokens = estimeate_token_count(&summary.text);
+        if current.len() >= 2 && current_tokens + tokens > token_limit {
+            groups.push(std::mem::take(&mut current));
+            current_tokens = 0;
+        }
+        current_tokens += tokens;
+        current.push(summary);
+    }
 
-    combine_summaries(client, &analysis.context, &summaries.join("\n\n")).await
+    if !current.is_empty() {
+        // 避免最后剩下一条无法合并的孤立摘要
+        if current.len() == 1 {
+            if let Some(last_group) = groups.last_mut() {
+                last_group.append(&mut current);
+            } else {
+                groups.push(current);
+            }
+        } else {
+            groups.push(current);
+        }
+    }
+    groups
 }
 
 async fn generate_single_chunk_commit_message(
     client: &dyn LLMClient,
     analysis: &DiffAnalysis,
+    fence: &Fence,
 ) -> Result<String> {
     let template = get_prompt_template("commit").await?;
     let (system_prompt, user_prompt) = parse_prompt_template(&template)?;
 
-    let user_prompt = build_user_prompt(&user_prompt, &analysis.context, &analysis.chunks[0]);
+    let system_prompt = fence.harden_system_prompt(&system_prompt);
+    let user_prompt =
+        build_user_prompt(&user_prompt, &analysis.context, &analysis.chunks[0], fence);
 
-    let message = client.call(&system_prompt, &user_prompt).await?;
+    let message = call_task(client, "commit", &system_prompt, &user_prompt).await?;
     extract_content(&message, "commit_message")
         .ok_or_else(|| anyhow!("LLM 无法从单个块生成有效的提交信息。"))
 }
 
+/// Pull Request 的标题和 Markdown 描述
+#[derive(Debug, Clone)]
+pub struct PullRequestDraft {
+    pub title: String,
+    pub body: String,
+}
+
+/// 根据分支相对 `base` 的 diff、提交记录和仓库的 PR 模板生成 PR 标题和描述。
+/// diff 过大时沿用提交信息的分块总结流程，用摘要代替原始 diff。
+pub async fn generate_pr_description(
+    client: &dyn LLMClient,
+    diff: &str,
+    base: &str,
+    commits: &str,
+    pr_template: Option<&str>,
+    context: &ContextConfig,
+) -> Result<PullRequestDraft> {
+    let progress_bar = ProgressBar::new_spinner();
+    progress_bar.set_style(
+        ProgressStyle::with_template("{spinner:.green} {msg}")
+            .unwrap()
+            .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏"),
+    );
+    progress_bar.enable_steady_tick(Duration::from_millis(100));
+    track_progress(&progress_bar);
+    progress_bar.set_message("Analyzing changes...");
+
+    progress_bar.suspend(|| guard::warn_if_suspicious("分支的 diff", diff));
+
+    // 提交记录和 PR 模板也在同一个提示词中，先从预算中扣除
+    let mut model_config = client.model_config().clone();
+    model_config.reserved_tokens +=
+        estimeate_token_count(commits) + estimeate_token_count(pr_template.unwrap_or("(none)"));
+    let analysis = crate::git::analyze_diff(diff, &model_config, context).await?;
+    let fence = Fence::new();
+
+    let changes = if analysis.needs_chunking {
+        let summaries =
+            summarize_changes(client, &model_config, &analysis, &progress_bar, &fence).await?;
+        progress_bar.set_style(ProgressStyle::with_template("{spinner:.green} {msg}").unwrap());
+        summaries
+    } else {
+        analysis.chunks[0].content.clone()
+    };
+    progress_bar.set_message("Writing pull request...");
+
+    let template = get_prompt_template("pr").await?;
+    let (system_prompt, user_prompt) = parse_prompt_template(&template)?;
+    let context = &analysis.context;
+    let system_prompt = fence.harden_system_prompt(&system_prompt);
+    let user_prompt = user_prompt
+        .replace("{base}", base)
+        .replace("{project_tree}", &context.project_tree)
+        .replace("{total_files}", &context.total_files.to_string())
+        .replace("{affected_files}", &context.affected_files.join(", "))
+        .replace("{commits}", &fence.wrap(commits))
+        .replace(
+            "{symbol_changes}",
+            &fence.wrap(&context.symbol_changes(&context.affected_files)),
+        )
+        .replace("{changes}", &fence.wrap(&changes))
+        .replace(
+            "{pr_template}",
+            &pr_template.map_or_else(|| "(none)".to_string(), |template| fence.wrap(template)),
+        );
+
+    let response = call_task(client, "pr", &system_prompt, &user_prompt).await?;
+    progress_bar.finish_with_message("✓ Pull request generated.");
+
+    let title = extract_content(&response, "pr_title")
+        .ok_or_else(|| anyhow!("LLM 没有返回有效的 PR 标题。"))?;
+    let body = extract_content(&response, "pr_body")
+        .ok_or_else(|| anyhow!("LLM 没有返回有效的 PR 描述。"))?;
+    Ok(PullRequestDraft {
+        title: title.lines().next().unwrap_or_default().trim().to_string(),
+        body,
+    })
+}
+
+/// 润色变更日志条目的措辞，版本标题和章节结构保持不变
+pub async fn polish_changelog(client: &dyn LLMClient, draft: &str) -> Result<String> {
+    let progress_bar = ProgressBar::new_spinner();
+    progress_bar.set_style(
+        ProgressStyle::with_template("{spinner:.green} {msg}")
+            .unwrap()
+            .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏"),
+    );
+    progress_bar.enable_steady_tick(Duration::from_millis(100));
+    track_progress(&progress_bar);
+    progress_bar.set_message("Polishing changelog...");
+
+    let fence = Fence::new();
+    let template = get_prompt_template("changelog").await?;
+    let (system_prompt, user_prompt) = parse_prompt_template(&template)?;
+    let system_prompt = fence.harden_system_prompt(&system_prompt);
+    let user_prompt = user_prompt.replace("{changelog}", &fence.wrap(draft));
+
+    let response = call_task(client, "changelog", &system_prompt, &user_prompt).await?;
+    progress_bar.finish_with_message("✓ Changelog polished.");
+
+    extract_content(&response, "changelog").ok_or_else(|| anyhow!("LLM 没有返回有效的变更日志。"))
+}
+
+/// 根据变更日志草稿和提交记录撰写面向使用者的发布说明
+pub async fn generate_release_notes(
+    client: &dyn LLMClient,
+    tag: &str,
+    previous_tag: Option<&str>,
+    changelog: &str,
+    commits: &str,
+) -> Result<String> {
+    let progress_bar = ProgressBar::new_spinner();
+    progress_bar.set_style(
+        ProgressStyle::with_template("{spinner:.green} {msg}")
+            .unwrap()
+            .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏"),
+    );
+    progress_bar.enable_steady_tick(Duration::from_millis(100));
+    track_progress(&progress_bar);
+    progress_bar.set_message("Writing release notes...");
+
+    let fence = Fence::new();
+    let template = get_prompt_template("release").await?;
+    let (system_prompt, user_prompt) = parse_prompt_template(&template)?;
+    let system_prompt = fence.harden_system_prompt(&system_prompt);
+    let user_prompt = user_prompt
+        .replace("{tag}", tag)
+        .replace("{previous_tag}", previous_tag.unwrap_or("(none)"))
+        .replace("{changelog}", &fence.wrap(changelog))
+        .replace("{commits}", &fence.wrap(commits));
+
+    let response = call_task(client, "release", &system_prompt, &user_prompt).await?;
+    progress_bar.finish_with_message("✓ Release notes generated.");
+
+    extract_content(&response, "release_notes")
+        .ok_or_else(|| anyhow!("LLM 没有返回有效的发布说明。"))
+}
+
 async fn summarize_chunk(
     client: &dyn LLMClient,
     context: &ProjectContext,
     chunk: &DiffChunk,
+    fence: &Fence,
 ) -> Result<String> {
     let template = get_prompt_template("summarize").await?;
     let (system_prompt, user_prompt) = parse_prompt_template(&template)?;
 
-    let user_prompt = build_summarize_user_prompt(&user_prompt, context, chunk);
+    let system_prompt = fence.harden_system_prompt(&system_prompt);
+    let user_prompt = build_summarize_user_prompt(&user_prompt, context, chunk, fence);
 
-    let summary = client.call(&system_prompt, &user_prompt).await?;
+    let summary = call_task(client, "summarize", &system_prompt, &user_prompt).await?;
     extract_content(&summary, "summary").ok_or_else(|| anyhow!("LLM 无法为代码块生成有效的摘要。"))
 }
 
@@ -145,13 +563,22 @@ async fn combine_summaries(
     client: &dyn LLMClient,
     context: &ProjectContext,
     summaries: &str,
+    fence: &Fence,
 ) -> Result<String> {
     let template = get_prompt_template("combine").await?;
     let (system_prompt, user_prompt) = parse_prompt_template(&template)?;
 
-    let user_prompt = build_combine_user_prompt(&user_prompt, context, summaries);
+    let system_prompt = fence.harden_system_prompt(&system_prompt);
+    let user_prompt = build_combine_user_prompt(
+        &user_prompt,
+        context,
+        &fence.wrap(&context.symbol_changes(&context.affected_files)),
+        &fence.wrap(&context.file_history(&context.affected_files)),
+        &fence.wrap(&context.style_guide(&context.affected_files)),
+        &fence.wrap(summaries),
+    );
 
-    let message = client.call(&system_prompt, &user_prompt).await?;
+    let message = call_task(client, "combine", &system_prompt, &user_prompt).await?;
     extract_content(&message, "commit_message")
         .ok_or_else(|| anyhow!("LLM 无法将摘要合并为最终的提交信息。"))
 }
@@ -183,30 +610,62 @@ pub(crate) fn parse_prompt_template(template: &str) -> Result<(String, String)>
     ))
 }
 
-fn build_user_prompt(template: &str, context: &ProjectContext, chunk: &DiffChunk) -> String {
+fn build_user_prompt(
+    template: &str,
+    context: &ProjectContext,
+    chunk: &DiffChunk,
+    fence: &Fence,
+) -> String {
     template
         .replace("{project_tree}", &context.project_tree)
         .replace("{total_files}", &context.total_files.to_string())
         .replace("{affected_files}", &context.affected_files.join(", "))
-        .replace("{diff_content}", &chunk.content)
+        .replace(
+            "{symbol_changes}",
+            &fence.wrap(&context.symbol_changes(&chunk.files)),
+        )
+        .replace(
+            "{file_history}",
+            &fence.wrap(&context.file_history(&chunk.files)),
+        )
+        .replace(
+            "{style_guide}",
+            &fence.wrap(&context.style_guide(&chunk.files)),
+        )
+        .replace("{diff_content}", &fence.wrap(&chunk.content))
 }
 
 fn build_summarize_user_prompt(
     template: &str,
     context: &ProjectContext,
     chunk: &DiffChunk,
+    fence: &Fence,
 ) -> String {
     template
         .replace("{total_files}", &context.total_files.to_string())
         .replace("{chunk_files}", &chunk.files.join(", "))
-        .replace("{diff_content}", &chunk.content)
+        .replace(
+            "{symbol_changes}",
+            &fence.wrap(&context.symbol_changes(&chunk.files)),
+        )
+        .replace("{diff_content}", &fence.wrap(&chunk.content))
 }
 
-fn build_combine_user_prompt(template: &str, context: &ProjectContext, summaries: &str) -> String {
+fn build_combine_user_prompt(
+    template: &str,
+    context: &ProjectContext,
+    symbol_changes: &str,
+    file_history: &str,
+    style_guide: &str,
+    summaries: &str,
+) -> String {
     template
         .replace("{project_tree}", &context.project_tree)
         .replace("{total_files}", &context.total_files.to_string())
         .replace("{affected_files}", &context.affected_files.join(", "))
+        .replace("{symbol_changes}", symbol_changes)
+        .replace("{file_history}", file_history)
+        .replace("{style_guide}", style_guide)
         .replace("{summaries}", summaries)
 }
 
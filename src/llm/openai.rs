@@ -1,10 +1,13 @@
 //! src/llm/openai.rs
 use super::LLMClient;
+use super::conversation::Message;
+use super::http::build_http_client;
 use crate::config::{ModelConfig, OpenAIProvider};
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::Duration;
 use tokio::time::sleep;
 
@@ -38,8 +41,6 @@ struct MessageContent {
     content: String,
 }
 
-const FAKE_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36";
-
 // --- Client Implementation ---
 pub struct OpenAIClient {
     api_key: String,
@@ -47,6 +48,7 @@ pub struct OpenAIClient {
     api_base: String,
     client: Client,
     model_config: ModelConfig,
+    task_timeouts: HashMap<String, u64>,
 }
 
 impl OpenAIClient {
@@ -64,15 +66,7 @@ impl OpenAIClient {
             .ok_or_else(|| anyhow!("Configuration for model '{}' not found, and no default configuration available.", model_name))?
             .clone();
 
-        let mut client_builder = Client::builder().user_agent(FAKE_USER_AGENT);
-
-        if let Some(proxy_url) = &config.proxy {
-            let proxy = reqwest::Proxy::all(proxy_url)
-                .map_err(|e| anyhow!("Failed to create proxy: {}", e))?;
-            client_builder = client_builder.proxy(proxy);
-        }
-
-        let client = client_builder.build()?;
+        let client = build_http_client(&config.http)?;
 
         Ok(Self {
             api_key,
@@ -80,6 +74,7 @@ impl OpenAIClient {
             api_base: format!("{}/chat/completions", api_base.trim_end_matches('/')),
             client,
             model_config,
+            task_timeouts: config.http.task_timeouts.clone(),
         })
     }
 }
@@ -90,23 +85,27 @@ impl LLMClient for OpenAIClient {
         &self.model_config
     }
 
+    fn task_timeout(&self, task: &str) -> Option<Duration> {
+        self.task_timeouts.get(task).copied().map(Duration::from_secs)
+    }
+
     async fn call(&self, system_prompt: &str, user_prompt: &str) -> Result<String> {
-        self.call_with_retry(system_prompt, user_prompt, 3).await
+        let messages = [Message::system(system_prompt), Message::user(user_prompt)];
+        self.call_with_retry(&messages, 3).await
+    }
+
+    async fn chat(&self, messages: &[Message]) -> Result<String> {
+        self.call_with_retry(messages, 3).await
     }
 }
 
 impl OpenAIClient {
     /// 带重试机制的 API 调用
-    async fn call_with_retry(
-        &self,
-        system_prompt: &str,
-        user_prompt: &str,
-        max_retries: usize,
-    ) -> Result<String> {
+    async fn call_with_retry(&self, messages: &[Message], max_retries: usize) -> Result<String> {
         let mut last_error = None;
 
         for attempt in 1..=max_retries {
-            match self.make_api_call(system_prompt, user_prompt).await {
+            match self.make_api_call(messages).await {
                 Ok(response) => return Ok(response),
                 Err(e) => {
                     last_error = Some(e);
@@ -129,19 +128,16 @@ impl OpenAIClient {
     }
 
     /// 执行单次 API 调用
-    async fn make_api_call(&self, system_prompt: &str, user_prompt: &str) -> Result<String> {
+    async fn make_api_call(&self, messages: &[Message]) -> Result<String> {
         let request_payload = OpenAIRequest {
             model: &self.model_name,
-            messages: vec![
-                ChatMessage {
-                    role: "system",
-                    content: system_prompt,
-                },
-                ChatMessage {
-                    role: "user",
-                    content: user_prompt,
-                },
-            ],
+            messages: messages
+                .iter()
+                .map(|message| ChatMessage {
+                    role: message.role.as_str(),
+                    content: &message.content,
+                })
+                .collect(),
             temperature: 0.7,
         };
 
@@ -150,12 +146,11 @@ impl OpenAIClient {
             .post(&self.api_base)
             .bearer_auth(&self.api_key)
             .json(&request_payload)
-            .timeout(Duration::from_secs(120)) // 2分钟超时
             .send()
             .await
             .map_err(|e| {
                 if e.is_timeout() {
-                    anyhow!("LLM API 调用超时 (120秒)")
+                    anyhow!("LLM API 调用超时: {}", e)
                 } else if e.is_connect() {
                     anyhow!("无法连接到 LLM API 服务器: {}", e)
                 } else {
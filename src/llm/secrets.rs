@@ -0,0 +1,281 @@
+//! src/llm/secrets.rs
+//!
+//! 在 diff 和文件内容发送给 LLM 之前，检测其中疑似密钥的内容（正则 + 熵值），
+//! 按配置替换为占位符、阻止发送或直接放行。误报可以写进 `.matecode-secrets-allow`。
+use crate::config::{self, SecretsMode};
+use crate::git;
+use anyhow::{Result, anyhow};
+use colored::Colorize;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashSet;
+
+pub const ALLOWLIST_FILE_NAME: &str = ".matecode-secrets-allow";
+
+struct Detector {
+    kind: &'static str,
+    regex: Regex,
+    /// 真正需要隐藏的部分所在的捕获组
+    group: usize,
+    /// 需要达到的最小香农熵，用来过滤 `password = "changeme"` 这类普通字符串
+    min_entropy: Option<f64>,
+}
+
+impl Detector {
+    fn new(kind: &'static str, pattern: &str, group: usize, min_entropy: Option<f64>) -> Self {
+        Self {
+            kind,
+            regex: Regex::new(pattern).expect("invalid secret pattern"),
+            group,
+            min_entropy,
+        }
+    }
+}
+
+lazy_static! {
+    static ref DETECTORS: Vec<Detector> = vec![
+        Detector::new(
+            "private key",
+            // 只匹配完整的密钥：头部之后只能是 base64 行或 `Proc-Type:` 这类头字段 (可以带 diff 的行前缀)，
+            // 并以 END 行结束；只提到头部的解析代码、文档和测试不受影响
+            r"(-----BEGIN[A-Z ]*PRIVATE KEY-----[ \t]*\r?\n(?:[+ -]?(?:[A-Za-z0-9+/=]*|[A-Za-z-]+:[^\n]*)\r?\n)*?[+ -]?-----END[A-Z ]*PRIVATE KEY-----)",
+            1,
+            None,
+        ),
+        Detector::new("AWS access key", r"\b((?:AKIA|ASIA)[0-9A-Z]{16})\b", 1, None),
+        Detector::new(
+            "AWS secret key",
+            r#"(?i)aws[\w.-]*(?:secret|access)[\w.-]*['"]?\s*[:=]\s*['"]?([A-Za-z0-9/+=]{40})"#,
+            1,
+            Some(3.5),
+        ),
+        Detector::new(
+            "GitHub token",
+            r"\b((?:ghp|gho|ghu|ghs|ghr)_[A-Za-z0-9]{36,255}|github_pat_[A-Za-z0-9_]{22,255})\b",
+            1,
+            None,
+        ),
+        Detector::new(
+            "JWT",
+            r"\b(eyJ[A-Za-z0-9_-]{8,}\.eyJ[A-Za-z0-9_-]{8,}\.[A-Za-z0-9_-]{8,})",
+            1,
+            None,
+        ),
+        Detector::new(
+            "connection string password",
+            r#"\b[a-zA-Z][a-zA-Z0-9+.-]*://[^\s:/@'"]+:([^\s@/'"]+)@"#,
+            1,
+            None,
+        ),
+        Detector::new(
+            "generic secret",
+            r#"(?i)[\w.-]*(?:secret|token|passwd|password|api[_-]?key|access[_-]?key|private[_-]?key)[\w.-]*['"]?\s*[:=]\s*['"]?([A-Za-z0-9/+_=.~-]{16,})"#,
+            1,
+            Some(3.5),
+        ),
+    ];
+}
+
+/// 一处疑似密钥
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub kind: &'static str,
+    /// 所在行号 (从 1 开始)
+    pub line: usize,
+    start: usize,
+    end: usize,
+    value: String,
+}
+
+impl Finding {
+    /// 只显示开头几个字符，避免在终端里再次泄露
+    pub fn masked(&self) -> String {
+        let prefix: String = self.value.chars().take(4).collect();
+        format!("{prefix}****")
+    }
+}
+
+/// 允许列表：每行一个确认无害的值，以 `regex:` 开头的行按正则匹配，`#` 开头为注释
+#[derive(Default)]
+pub struct Allowlist {
+    values: HashSet<String>,
+    patterns: Vec<Regex>,
+}
+
+impl Allowlist {
+    fn extend_from_str(&mut self, content: &str) -> Result<()> {
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.strip_prefix("regex:") {
+                Some(pattern) => self.patterns.push(
+                    Regex::new(pattern.trim())
+                        .map_err(|e| anyhow!("{ALLOWLIST_FILE_NAME} 中的正则无效 '{pattern}': {e}"))?,
+                ),
+                None => {
+                    self.values.insert(line.to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn allows(&self, value: &str) -> bool {
+        self.values.contains(value) || self.patterns.iter().any(|re| re.is_match(value))
+    }
+}
+
+/// 扫描文本中的疑似密钥，结果按位置排序且互不重叠
+pub fn scan_for_secrets(text: &str, allowlist: &Allowlist) -> Vec<Finding> {
+    let mut findings: Vec<Finding> = Vec::new();
+    for detector in DETECTORS.iter() {
+        for captures in detector.regex.captures_iter(text) {
+            let Some(secret) = captures.get(detector.group) else {
+                continue;
+            };
+            let value = secret.as_str();
+            if looks_like_placeholder(value)
+                || detector
+                    .min_entropy
+                    .is_some_and(|min| shannon_entropy(value) < min)
+                || allowlist.allows(value)
+            {
+                continue;
+            }
+            findings.push(Finding {
+                kind: detector.kind,
+                line: text[..secret.start()].matches('\n').count() + 1,
+                start: secret.start(),
+                end: secret.end(),
+                value: value.to_string(),
+            });
+        }
+    }
+
+    // 排在前面的检测器更具体，重叠时保留先出现的那一个
+    let mut accepted: Vec<Finding> = Vec::new();
+    for finding in findings {
+        if !accepted
+            .iter()
+            .any(|other| finding.start < other.end && other.start < finding.end)
+        {
+            accepted.push(finding);
+        }
+    }
+    accepted.sort_by_key(|finding| finding.start);
+    accepted
+}
+
+/// 把每处密钥替换为 `[REDACTED:<类型>]`
+pub fn redact(text: &str, findings: &[Finding]) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut last = 0;
+    for finding in findings {
+        redacted.push_str(&text[last..finding.start]);
+        redacted.push_str(&format!("[REDACTED:{}]", finding.kind));
+        last = finding.end;
+    }
+    redacted.push_str(&text[last..]);
+    redacted
+}
+
+fn looks_like_placeholder(value: &str) -> bool {
+    const PLACEHOLDERS: &[&str] = &[
+        "example", "your", "xxxx", "changeme", "placeholder", "dummy", "redacted",
+    ];
+    let lower = value.to_lowercase();
+    value.starts_with('$')
+        || value.starts_with('<')
+        || value.starts_with('{')
+        || PLACEHOLDERS.iter().any(|word| lower.contains(word))
+}
+
+fn shannon_entropy(value: &str) -> f64 {
+    let chars: Vec<char> = value.chars().collect();
+    if chars.is_empty() {
+        return 0.0;
+    }
+    let mut counts = std::collections::HashMap::new();
+    for c in &chars {
+        *counts.entry(c).or_insert(0usize) += 1;
+    }
+    let len = chars.len() as f64;
+    counts
+        .values()
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// 根据配置处理即将发送给 LLM 的内容
+pub struct SecretGuard {
+    mode: SecretsMode,
+    allowlist: Allowlist,
+}
+
+impl SecretGuard {
+    /// 读取配置中的模式，以及全局 (配置目录) 和仓库根目录下的允许列表
+    pub async fn load() -> Result<Self> {
+        let config = config::load_config().await?;
+        let mut allowlist = Allowlist::default();
+
+        let mut files = vec![config::get_config_dir().await?.join(ALLOWLIST_FILE_NAME)];
+        if let Ok(toplevel) = git::get_repo_root().await {
+            files.push(toplevel.join(ALLOWLIST_FILE_NAME));
+        }
+        for file in files {
+            if let Ok(content) = tokio::fs::read_to_string(&file).await {
+                allowlist.extend_from_str(&content)?;
+            }
+        }
+
+        Ok(Self {
+            mode: config.secrets.mode,
+            allowlist,
+        })
+    }
+
+    /// 返回可以安全发送的内容；`block` 模式下发现密钥会返回错误。
+    pub fn protect(&self, source: &str, text: &str) -> Result<String> {
+        if self.mode == SecretsMode::Allow {
+            return Ok(text.to_string());
+        }
+
+        let findings = scan_for_secrets(text, &self.allowlist);
+        if findings.is_empty() {
+            return Ok(text.to_string());
+        }
+
+        match self.mode {
+            SecretsMode::Block => {
+                print_findings(
+                    &format!("🔒 在 {source} 中发现 {} 处疑似密钥，已阻止发送给 LLM：", findings.len()),
+                    &findings,
+                );
+                Err(anyhow!(
+                    "检测到疑似密钥。如果是误报，请把对应的值加入 {ALLOWLIST_FILE_NAME}，或在配置中设置 [secrets] mode = \"redact\"。"
+                ))
+            }
+            _ => {
+                print_findings(
+                    &format!("🔒 在 {source} 中发现 {} 处疑似密钥，已替换为占位符：", findings.len()),
+                    &findings,
+                );
+                Ok(redact(text, &findings))
+            }
+        }
+    }
+}
+
+fn print_findings(title: &str, findings: &[Finding]) {
+    eprintln!("{}", title.yellow());
+    for finding in findings.iter().take(10) {
+        eprintln!("   {:>5} | {} ({})", finding.line, finding.kind, finding.masked());
+    }
+    if findings.len() > 10 {
+        eprintln!("   ... 还有 {} 处", findings.len() - 10);
+    }
+}
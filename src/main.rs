@@ -1,32 +1,120 @@
+mod cancel;
+mod changelog;
 mod commands;
+mod compact;
 mod config;
+mod diff;
 mod git;
+mod git_backend;
 mod history;
+mod ignore_rules;
+mod issue;
 mod llm;
+mod manifest;
+mod project_tree;
+mod style;
+mod symbols;
 
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow};
 use clap::Parser;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = commands::Cli::parse();
 
+    cancel::spawn_interrupt_watchdog();
+
+    // Ctrl-C 时丢弃正在执行的命令，所有未完成的 LLM 请求都会随之取消
+    tokio::select! {
+        result = run(cli) => result,
+        _ = cancel::wait_for_interrupt() => cancel::exit_cancelled(),
+    }
+}
+
+async fn run(cli: commands::Cli) -> Result<()> {
+    if let commands::Commands::Archive {
+        backfill: true,
+        since,
+        author,
+        ..
+    } = &cli.command
+        && cli.repo.len() > 1
+    {
+        return commands::archive::backfill_repos(&cli.repo, since.as_deref(), author.as_deref())
+            .await;
+    }
+
+    // 仓库位置在第一次使用时按当前目录解析，所以要在执行任何命令之前切换
+    match cli.repo.as_slice() {
+        [] => {}
+        [repo] => std::env::set_current_dir(repo)
+            .with_context(|| format!("无法进入目录: {}", repo.display()))?,
+        _ => return Err(anyhow!("只有 archive --backfill 可以指定多个 --repo。")),
+    }
+
     match cli.command {
         commands::Commands::Init => commands::init::handle_init().await?,
         commands::Commands::Commit {
             all,
             structured,
             no_edit,
-        } => commands::commit::handle_commit(all, structured, no_edit).await?,
+            print,
+            range,
+            working_tree,
+            pathspec,
+        } => {
+            let source = match range {
+                Some(range) => git_backend::DiffSource::Range(range),
+                None if working_tree => git_backend::DiffSource::WorkingTree,
+                None => git_backend::DiffSource::Staged,
+            };
+            let scope = git_backend::DiffScope { source, pathspec };
+            commands::commit::handle_commit(all, structured, no_edit, print, scope).await?
+        }
+        commands::Commands::Pr { base, output } => {
+            commands::pr::handle_pr(base, output).await?
+        }
+        commands::Commands::Changelog {
+            from,
+            to,
+            polish,
+            write,
+        } => commands::changelog::handle_changelog(from, to, polish, write).await?,
+        commands::Commands::Release { yes } => commands::release::handle_release(yes).await?,
         commands::Commands::Report {
             since,
             until,
             period,
         } => commands::report::handler_report(since, until, period).await?,
-        commands::Commands::Archive => commands::archive::handle_archive().await?,
+        commands::Commands::Archive {
+            backfill: true,
+            since,
+            author,
+            ..
+        } => {
+            let repo = git_backend::repo_context()
+                .await
+                .context("无法获取用于归档的项目名称。")?;
+            let backend = git_backend::backend().await?;
+            commands::archive::handle_backfill(
+                backend.as_ref(),
+                repo,
+                since.as_deref(),
+                author.as_deref(),
+            )
+            .await?
+        }
+        commands::Commands::Archive { rewritten, .. } => {
+            commands::archive::handle_archive(rewritten).await?
+        }
         commands::Commands::InstallHook => {
             commands::install_hook::install_post_commit_hook().await?
         }
+        commands::Commands::Ignore { action } => match action {
+            commands::IgnoreAction::Check { path } => {
+                commands::ignore::handle_ignore_check(&path).await?
+            }
+        },
         commands::Commands::Understand { dir } => {
             commands::understand::handle_understand(dir).await?
         }
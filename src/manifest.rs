@@ -0,0 +1,254 @@
+//! src/manifest.rs
+//!
+//! 读取和更新项目清单 (Cargo.toml、package.json、pyproject.toml) 中的版本号。
+//! 只替换版本号本身，文件的其余格式和注释保持不变。Cargo.toml 旁边的 Cargo.lock
+//! 中记录的本地包版本号会一起更新。
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+lazy_static! {
+    static ref TOML_VERSION: Regex = Regex::new(r#"^\s*version\s*=\s*"([^"]*)""#).unwrap();
+    static ref TOML_NAME: Regex = Regex::new(r#"^\s*name\s*=\s*"([^"]*)""#).unwrap();
+    static ref TOML_SOURCE: Regex = Regex::new(r"^\s*source\s*=").unwrap();
+    static ref JSON_VERSION: Regex = Regex::new(r#"^\s*:\s*"([^"]*)""#).unwrap();
+}
+
+/// TOML 清单以及其中可能存放版本号的表，按优先级排列
+const TOML_MANIFESTS: &[(&str, &[&str])] = &[
+    ("Cargo.toml", &["package", "workspace.package"]),
+    ("pyproject.toml", &["project", "tool.poetry"]),
+];
+
+const PACKAGE_JSON: &str = "package.json";
+const CARGO_TOML: &str = "Cargo.toml";
+const CARGO_LOCK: &str = "Cargo.lock";
+
+/// 一个写有版本号的项目清单
+#[derive(Debug)]
+pub struct Manifest {
+    pub path: PathBuf,
+    pub version: String,
+    content: String,
+    /// 版本号在 `content` 中的位置
+    span: Range<usize>,
+    lock: Option<Lockfile>,
+}
+
+/// 需要随清单一起更新版本号的锁文件
+#[derive(Debug)]
+struct Lockfile {
+    path: PathBuf,
+    content: String,
+    /// 各个版本号在 `content` 中的位置
+    spans: Vec<Range<usize>>,
+}
+
+impl Manifest {
+    fn new(path: PathBuf, content: String, span: Range<usize>) -> Self {
+        Manifest {
+            path,
+            version: content[span.clone()].to_string(),
+            content,
+            span,
+            lock: None,
+        }
+    }
+
+    /// 文件名，用于提示信息
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// 清单和需要一起更新的锁文件
+    pub fn paths(&self) -> Vec<&Path> {
+        let mut paths = vec![self.path.as_path()];
+        paths.extend(self.lock.as_ref().map(|lock| lock.path.as_path()));
+        paths
+    }
+
+    pub async fn write_version(&self, version: &str) -> Result<()> {
+        write_spans(
+            &self.path,
+            &self.content,
+            std::slice::from_ref(&self.span),
+            version,
+        )
+        .await?;
+        if let Some(lock) = &self.lock {
+            write_spans(&lock.path, &lock.content, &lock.spans, version).await?;
+        }
+        Ok(())
+    }
+}
+
+async fn write_spans(
+    path: &Path,
+    content: &str,
+    spans: &[Range<usize>],
+    value: &str,
+) -> Result<()> {
+    let mut content = content.to_string();
+    // 从后往前替换，前面的位置不受影响
+    for span in spans.iter().rev() {
+        content.replace_range(span.clone(), value);
+    }
+    tokio::fs::write(path, content)
+        .await
+        .with_context(|| format!("无法写入文件: {}", path.display()))
+}
+
+/// 仓库根目录下写有版本号的清单。版本号继承自 workspace 或由构建工具动态生成的清单会被忽略。
+pub async fn find_manifests(root: &Path) -> Result<Vec<Manifest>> {
+    let mut manifests = Vec::new();
+    for (file, tables) in TOML_MANIFESTS {
+        let path = root.join(file);
+        if let Some(content) = read_if_exists(&path).await?
+            && let Some((table, span)) = toml_value_span(&content, &TOML_VERSION, tables)
+        {
+            let mut manifest = Manifest::new(path, content, span);
+            if *file == CARGO_TOML {
+                // workspace 的版本号可能被多个成员继承，这时无法确定包名
+                let name = match table {
+                    "package" => toml_value_span(&manifest.content, &TOML_NAME, &[table])
+                        .map(|(_, span)| manifest.content[span].to_string()),
+                    _ => None,
+                };
+                manifest.lock = cargo_lockfile(root, name.as_deref(), &manifest.version).await?;
+            }
+            manifests.push(manifest);
+        }
+    }
+
+    let path = root.join(PACKAGE_JSON);
+    if let Some(content) = read_if_exists(&path).await?
+        && let Some(span) = json_version_span(&content)
+    {
+        manifests.push(Manifest::new(path, content, span));
+    }
+    Ok(manifests)
+}
+
+async fn read_if_exists(path: &Path) -> Result<Option<String>> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("无法读取 {}", path.display())),
+    }
+}
+
+/// 顶层对象中 `"version"` 的值，嵌套对象 (例如 `dependencies`) 中的同名键会被跳过
+fn json_version_span(content: &str) -> Option<Range<usize>> {
+    let mut depth = 0;
+    let mut chars = content.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth -= 1,
+            '"' => {
+                let start = index + 1;
+                let mut end = start;
+                let mut escaped = false;
+                for (index, c) in chars.by_ref() {
+                    match c {
+                        _ if escaped => escaped = false,
+                        '\\' => escaped = true,
+                        '"' => {
+                            end = index;
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                if depth == 1
+                    && &content[start..end] == "version"
+                    && let Some(value) = JSON_VERSION
+                        .captures(&content[end + 1..])
+                        .and_then(|captures| captures.get(1))
+                {
+                    return Some(end + 1 + value.start()..end + 1 + value.end());
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Cargo.lock 中版本号为 `version` 的本地包 (没有 `source`)，指定 `name` 时只匹配这个包
+async fn cargo_lockfile(
+    root: &Path,
+    name: Option<&str>,
+    version: &str,
+) -> Result<Option<Lockfile>> {
+    let path = root.join(CARGO_LOCK);
+    let Some(content) = read_if_exists(&path).await? else {
+        return Ok(None);
+    };
+    let mut spans = Vec::new();
+    let mut offset = 0;
+    for block in content.split_inclusive("[[package]]") {
+        let mut package_name = None;
+        let mut package_version = None;
+        let mut has_source = false;
+        let mut line_offset = offset;
+        for line in block.split_inclusive('\n') {
+            if let Some(value) = TOML_NAME.captures(line).and_then(|c| c.get(1)) {
+                package_name = Some(value.as_str());
+            } else if let Some(value) = TOML_VERSION.captures(line).and_then(|c| c.get(1)) {
+                package_version = Some((
+                    value.as_str(),
+                    line_offset + value.start()..line_offset + value.end(),
+                ));
+            } else if TOML_SOURCE.is_match(line) {
+                has_source = true;
+            }
+            line_offset += line.len();
+        }
+        if let (Some(package_name), Some((package_version, span))) = (package_name, package_version)
+            && !has_source
+            && package_version == version
+            && name.is_none_or(|name| name == package_name)
+        {
+            spans.push(span);
+        }
+        offset += block.len();
+    }
+    Ok((!spans.is_empty()).then_some(Lockfile {
+        path,
+        content,
+        spans,
+    }))
+}
+
+/// 在 `tables` 中按顺序查找 `key` 匹配的行，返回所在的表和第一个捕获组的范围
+fn toml_value_span<'a>(
+    content: &str,
+    key: &Regex,
+    tables: &[&'a str],
+) -> Option<(&'a str, Range<usize>)> {
+    let mut found: Vec<(usize, Range<usize>)> = Vec::new();
+    let mut table = String::new();
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            table = trimmed.trim_matches(['[', ']']).trim().to_string();
+        } else if let Some(rank) = tables.iter().position(|wanted| *wanted == table)
+            && let Some(value) = key.captures(line).and_then(|c| c.get(1))
+        {
+            found.push((rank, offset + value.start()..offset + value.end()));
+        }
+        offset += line.len();
+    }
+    found
+        .into_iter()
+        .min_by_key(|(rank, _)| *rank)
+        .map(|(rank, span)| (tables[rank], span))
+}
@@ -0,0 +1,188 @@
+//! src/project_tree.rs
+//!
+//! 为 LLM 生成一个有上限的项目目录树：本次修改涉及的目录排在最前面并展开，
+//! 其余大目录折叠成一行，整体大小受 token 预算限制。
+
+use crate::git::estimeate_token_count;
+use crate::git_backend;
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+/// 未涉及修改的目录最多展开到这一层
+const MAX_UNTOUCHED_DEPTH: usize = 2;
+/// 文件数超过该值且未涉及修改的目录会被折叠
+const COLLAPSE_THRESHOLD: usize = 30;
+/// 每个目录最多列出的文件数
+const MAX_FILES_PER_DIR: usize = 8;
+
+#[derive(Default)]
+struct DirNode {
+    dirs: BTreeMap<String, DirNode>,
+    files: Vec<String>,
+    total_files: usize,
+    touched: bool,
+}
+
+impl DirNode {
+    fn insert(&mut self, path: &str) {
+        self.total_files += 1;
+        match path.split_once('/') {
+            Some((dir, rest)) => self.dirs.entry(dir.to_string()).or_default().insert(rest),
+            None => self.files.push(path.to_string()),
+        }
+    }
+
+    fn mark_touched(&mut self, path: &str) {
+        self.touched = true;
+        if let Some((dir, rest)) = path.split_once('/')
+            && let Some(child) = self.dirs.get_mut(dir)
+        {
+            child.mark_touched(rest);
+        }
+    }
+}
+
+/// 列出仓库中的所有文件 (相对于仓库根目录)。
+/// 优先使用 git 跟踪的文件，失败时用 `ignore` 遍历工作区并遵守 .gitignore。
+pub async fn list_project_files() -> Result<Vec<String>> {
+    let files = git_backend::backend()
+        .await?
+        .tracked_files()
+        .await
+        .unwrap_or_default();
+    if !files.is_empty() {
+        return Ok(files);
+    }
+
+    let root = git_backend::repo_context().await?.toplevel.clone();
+    let walked = tokio::task::spawn_blocking(move || {
+        ignore::WalkBuilder::new(&root)
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+            .filter_map(|entry| {
+                entry
+                    .path()
+                    .strip_prefix(&root)
+                    .ok()
+                    .map(|p| p.to_string_lossy().replace('\\', "/"))
+            })
+            .collect::<Vec<_>>()
+    })
+    .await?;
+    Ok(walked)
+}
+
+/// 生成目录树文本，返回 (目录树, 项目文件总数)
+pub async fn build_project_tree(
+    affected_files: &[String],
+    token_budget: usize,
+) -> Result<(String, usize)> {
+    let files = list_project_files().await?;
+    Ok((render_tree(&files, affected_files, token_budget), files.len()))
+}
+
+pub fn render_tree(files: &[String], affected_files: &[String], token_budget: usize) -> String {
+    let mut root = DirNode::default();
+    for file in files {
+        root.insert(file);
+    }
+    for file in affected_files {
+        root.mark_touched(file);
+    }
+
+    let mut renderer = TreeRenderer {
+        lines: vec![format!(
+            "项目结构 (共 {} 个文件，* 表示本次修改涉及的目录或文件):",
+            files.len()
+        )],
+        tokens: 0,
+        token_budget,
+        truncated: false,
+        affected_files,
+    };
+    renderer.tokens = estimeate_token_count(&renderer.lines[0]);
+    renderer.render_dir(&root, "", 0);
+
+    if renderer.truncated {
+        renderer.lines.push("... (目录树已截断)".to_string());
+    }
+    renderer.lines.join("\n")
+}
+
+struct TreeRenderer<'a> {
+    lines: Vec<String>,
+    tokens: usize,
+    token_budget: usize,
+    truncated: bool,
+    affected_files: &'a [String],
+}
+
+impl TreeRenderer<'_> {
+    fn push_line(&mut self, line: String) -> bool {
+        if self.truncated {
+            return false;
+        }
+        let tokens = estimeate_token_count(&line) + 1;
+        if self.tokens + tokens > self.token_budget {
+            self.truncated = true;
+            return false;
+        }
+        self.tokens += tokens;
+        self.lines.push(line);
+        true
+    }
+
+    fn render_dir(&mut self, node: &DirNode, prefix: &str, depth: usize) {
+        let indent = "  ".repeat(depth);
+
+        // 涉及修改的目录优先
+        let mut dirs: Vec<(&String, &DirNode)> = node.dirs.iter().collect();
+        dirs.sort_by_key(|(_, child)| !child.touched);
+
+        for (name, child) in dirs {
+            let path = format!("{prefix}{name}/");
+            let mark = if child.touched { "*" } else { "" };
+            let collapse = !child.touched
+                && (depth >= MAX_UNTOUCHED_DEPTH || child.total_files > COLLAPSE_THRESHOLD);
+
+            if !self.push_line(format!(
+                "{indent}{mark}{name}/ ({} files)",
+                child.total_files
+            )) {
+                return;
+            }
+            if !collapse {
+                self.render_dir(child, &path, depth + 1);
+            }
+        }
+
+        self.render_files(node, prefix, &indent);
+    }
+
+    fn render_files(&mut self, node: &DirNode, prefix: &str, indent: &str) {
+        let (mut touched, others): (Vec<&String>, Vec<&String>) = node
+            .files
+            .iter()
+            .partition(|name| self.affected_files.contains(&format!("{prefix}{name}")));
+
+        let limit = MAX_FILES_PER_DIR.max(touched.len());
+        let shown_others = limit - touched.len();
+        let hidden = others.len().saturating_sub(shown_others);
+        touched.sort();
+
+        for name in &touched {
+            if !self.push_line(format!("{indent}*{name}")) {
+                return;
+            }
+        }
+        for name in others.iter().take(shown_others) {
+            if !self.push_line(format!("{indent}{name}")) {
+                return;
+            }
+        }
+        if hidden > 0 {
+            self.push_line(format!("{indent}... {hidden} more files"));
+        }
+    }
+}
@@ -0,0 +1,359 @@
+//! src/style.rs
+//!
+//! 从仓库最近的提交中总结提交信息的习惯 (语言、格式、type 和 scope、工单号、gitmoji、标题长度)，
+//! 作为 prompt 中的 `{style_guide}`，让生成的提交信息和仓库已有的风格保持一致。
+//! 总结结果缓存在 git 目录中，新增的提交不多时直接复用。
+
+use crate::changelog::Header;
+use crate::git_backend::{self, CommitInfo};
+use anyhow::Result;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// 参与分析的最近提交数
+const SAMPLE_SIZE: usize = 200;
+/// 提交太少时总结不出可靠的习惯
+const MIN_SAMPLES: usize = 5;
+/// 缓存之后新增这么多提交就重新分析
+const REFRESH_AFTER_COMMITS: usize = 20;
+/// 一种写法至少占这个比例 (百分比) 才算作仓库的习惯
+const HABIT_THRESHOLD: u8 = 50;
+/// 列出的 type 和 scope 数
+const MAX_LISTED: usize = 8;
+const CACHE_FILE: &str = "style.json";
+
+lazy_static! {
+    static ref TICKET: Regex = Regex::new(r"\b([A-Z][A-Z0-9]+)-\d+\b").unwrap();
+    static ref BRACKET_TICKET: Regex = Regex::new(r"^\[[A-Z][A-Z0-9]+-\d+\]\s*").unwrap();
+    static ref PREFIX_TICKET: Regex = Regex::new(r"^[A-Z][A-Z0-9]+-\d+:?\s+").unwrap();
+    static ref GITMOJI_CODE: Regex = Regex::new(r"^:[a-z0-9_+-]+:").unwrap();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubjectLanguage {
+    Chinese,
+    English,
+    Mixed,
+}
+
+/// 工单号在标题中的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TicketPlacement {
+    /// `[PROJ-123] subject`
+    Bracket,
+    /// `PROJ-123: subject`
+    Prefix,
+    /// 出现在标题的其他位置，例如 `subject (PROJ-123)`
+    Inline,
+}
+
+impl TicketPlacement {
+    fn example(self) -> &'static str {
+        match self {
+            Self::Bracket => "[PROJ-123] subject",
+            Self::Prefix => "PROJ-123: subject",
+            Self::Inline => "subject (PROJ-123)",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TicketStyle {
+    pub placement: TicketPlacement,
+    /// 带有工单号的提交占比 (百分比)
+    pub share: u8,
+    /// 出现过的工单项目前缀，按次数排序
+    pub keys: Vec<String>,
+}
+
+/// 一个仓库的提交习惯
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StyleProfile {
+    /// 分析时的 HEAD
+    pub head: String,
+    pub sample_size: usize,
+    pub language: SubjectLanguage,
+    /// 遵循 Conventional Commits 的提交占比 (百分比)
+    pub conventional_share: u8,
+    /// 用过的 type 及次数，按次数排序
+    pub types: Vec<(String, usize)>,
+    /// 用过的 scope 及次数，按次数排序
+    pub scopes: Vec<(String, usize)>,
+    pub ticket: Option<TicketStyle>,
+    /// 以 gitmoji 开头的提交占比 (百分比)
+    pub gitmoji_share: u8,
+    /// 标题的平均字符数
+    pub average_subject_length: usize,
+    /// 最近几条有代表性的标题
+    pub examples: Vec<String>,
+}
+
+impl StyleProfile {
+    /// `commits` 从新到旧；合并提交和 fixup 提交不反映手写的习惯，不参与统计
+    pub fn analyze(head: &str, commits: &[CommitInfo]) -> Option<Self> {
+        let subjects: Vec<&str> = commits
+            .iter()
+            .map(|commit| commit.subject().trim())
+            .filter(|subject| {
+                !subject.is_empty()
+                    && !subject.starts_with("Merge ")
+                    && !subject.starts_with("fixup!")
+                    && !subject.starts_with("squash!")
+            })
+            .collect();
+        if subjects.len() < MIN_SAMPLES {
+            return None;
+        }
+
+        let mut types: HashMap<String, usize> = HashMap::new();
+        let mut scopes: HashMap<String, usize> = HashMap::new();
+        let mut ticket_keys: HashMap<String, usize> = HashMap::new();
+        let mut placements: HashMap<TicketPlacement, usize> = HashMap::new();
+        let (mut conventional, mut gitmoji, mut chinese, mut english) = (0, 0, 0, 0);
+        let mut total_length = 0;
+        let mut examples = Vec::new();
+
+        for subject in &subjects {
+            total_length += subject.chars().count();
+
+            let mut rest = *subject;
+            if let Some(key) = TICKET.captures(subject).map(|c| c[1].to_string()) {
+                *ticket_keys.entry(key).or_default() += 1;
+                let placement = if let Some(found) = BRACKET_TICKET.find(subject) {
+                    rest = &subject[found.end()..];
+                    TicketPlacement::Bracket
+                } else if let Some(found) = PREFIX_TICKET.find(subject) {
+                    rest = &subject[found.end()..];
+                    TicketPlacement::Prefix
+                } else {
+                    TicketPlacement::Inline
+                };
+                *placements.entry(placement).or_default() += 1;
+            }
+
+            if starts_with_gitmoji(rest) {
+                gitmoji += 1;
+            }
+            let description = match Header::parse(rest) {
+                Some(header) => {
+                    conventional += 1;
+                    *types.entry(header.kind.to_lowercase()).or_default() += 1;
+                    if let Some(scope) = header.scope {
+                        *scopes.entry(scope).or_default() += 1;
+                    }
+                    header.description
+                }
+                None => rest.to_string(),
+            };
+            match detect_language(&description) {
+                Some(SubjectLanguage::Chinese) => chinese += 1,
+                Some(_) => english += 1,
+                None => {}
+            }
+            if examples.len() < 5 && !examples.contains(&subject.to_string()) {
+                examples.push(subject.to_string());
+            }
+        }
+
+        let total = subjects.len();
+        let share = |count: usize| (count * 100 / total) as u8;
+        let language = if share(chinese) >= 70 {
+            SubjectLanguage::Chinese
+        } else if share(english) >= 70 {
+            SubjectLanguage::English
+        } else {
+            SubjectLanguage::Mixed
+        };
+        let ticket_count: usize = placements.values().sum();
+        let ticket = (ticket_count > 0).then(|| TicketStyle {
+            placement: placements
+                .into_iter()
+                .max_by_key(|(_, count)| *count)
+                .map(|(placement, _)| placement)
+                .unwrap_or(TicketPlacement::Inline),
+            share: share(ticket_count),
+            keys: by_frequency(ticket_keys)
+                .into_iter()
+                .take(3)
+                .map(|(key, _)| key)
+                .collect(),
+        });
+
+        Some(StyleProfile {
+            head: head.to_string(),
+            sample_size: total,
+            language,
+            conventional_share: share(conventional),
+            types: by_frequency(types),
+            scopes: by_frequency(scopes),
+            ticket,
+            gitmoji_share: share(gitmoji),
+            average_subject_length: total_length / total,
+            examples,
+        })
+    }
+
+    /// 已有的 scope 中和本次改动的路径对应的那些，按使用次数排序
+    pub fn suggest_scopes(&self, affected_files: &[String]) -> Vec<String> {
+        self.scopes
+            .iter()
+            .filter(|(scope, _)| {
+                let scope = scope.to_lowercase();
+                affected_files.iter().any(|file| {
+                    file.to_lowercase().split('/').any(|segment| {
+                        segment == scope || segment.split('.').next() == Some(scope.as_str())
+                    })
+                })
+            })
+            .map(|(scope, _)| scope.clone())
+            .take(3)
+            .collect()
+    }
+
+    /// prompt 中的 `{style_guide}`
+    pub fn render(&self, affected_files: &[String]) -> String {
+        let listed = |items: &[(String, usize)]| {
+            items
+                .iter()
+                .take(MAX_LISTED)
+                .map(|(name, count)| format!("{name} ({count})"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let mut lines = vec![format!(
+            "以下是根据本仓库最近 {} 条提交总结的习惯:",
+            self.sample_size
+        )];
+        lines.push(format!(
+            "- 语言: {}",
+            match self.language {
+                SubjectLanguage::Chinese => "标题的描述部分使用中文",
+                SubjectLanguage::English => "标题的描述部分使用英文",
+                SubjectLanguage::Mixed => "中英文都有使用，跟随与本次改动相近的提交",
+            }
+        ));
+        if self.conventional_share >= HABIT_THRESHOLD {
+            lines.push(format!(
+                "- 格式: {}% 的提交遵循 Conventional Commits，使用过的 type: {}",
+                self.conventional_share,
+                listed(&self.types)
+            ));
+        } else {
+            lines.push(format!(
+                "- 格式: 只有 {}% 的提交使用 `type(scope): subject` 形式，不要强行套用，参考下面的示例",
+                self.conventional_share
+            ));
+        }
+        if !self.scopes.is_empty() {
+            lines.push(format!("- 使用过的 scope: {}", listed(&self.scopes)));
+            let suggested = self.suggest_scopes(affected_files);
+            lines.push(if suggested.is_empty() {
+                "- 本次改动的路径没有对应的已有 scope，从上面选择最接近的一个，或者省略 scope，不要发明新的写法".to_string()
+            } else {
+                format!("- 本次改动建议使用的 scope: {}", suggested.join(", "))
+            });
+        }
+        if let Some(ticket) = &self.ticket
+            && ticket.share >= HABIT_THRESHOLD
+        {
+            lines.push(format!(
+                "- 工单号: {}% 的提交带有工单号，写法为 `{}`，常见前缀: {}；不知道工单号时不要编造",
+                ticket.share,
+                ticket.placement.example(),
+                ticket.keys.join(", ")
+            ));
+        }
+        if self.gitmoji_share >= HABIT_THRESHOLD {
+            lines.push(format!(
+                "- gitmoji: {}% 的标题以 gitmoji 开头，选择与改动类型相符的一个",
+                self.gitmoji_share
+            ));
+        }
+        lines.push(format!(
+            "- 长度: 标题平均 {} 个字符",
+            self.average_subject_length
+        ));
+        lines.push("- 最近的示例:".to_string());
+        lines.extend(self.examples.iter().map(|example| format!("  - {example}")));
+        lines.join("\n")
+    }
+}
+
+/// 当前仓库的提交习惯，优先使用缓存；提交太少时返回 `None`
+pub async fn load_profile() -> Result<Option<StyleProfile>> {
+    let backend = git_backend::backend().await?;
+    let Some(head) = backend.log(1).await?.into_iter().next() else {
+        return Ok(None);
+    };
+    let cache_path = cache_path().await?;
+
+    if let Ok(content) = tokio::fs::read_to_string(&cache_path).await
+        && let Ok(cached) = serde_json::from_str::<StyleProfile>(&content)
+    {
+        // 缓存的 HEAD 被 rebase 掉之后无法比较，直接重新分析
+        let fresh = cached.head == head.sha
+            || backend
+                .commits_between(Some(&cached.head), "HEAD")
+                .await
+                .is_ok_and(|commits| commits.len() < REFRESH_AFTER_COMMITS);
+        if fresh {
+            return Ok(Some(cached));
+        }
+    }
+
+    let commits = backend.log(SAMPLE_SIZE).await?;
+    let profile = StyleProfile::analyze(&head.sha, &commits);
+    if let Some(profile) = &profile
+        && let Some(parent) = cache_path.parent()
+    {
+        // 缓存写不进去不影响生成
+        let _ = tokio::fs::create_dir_all(parent).await;
+        let _ = tokio::fs::write(&cache_path, serde_json::to_string_pretty(profile)?).await;
+    }
+    Ok(profile)
+}
+
+/// 放在共享的 git 目录中，同一仓库的各个工作区共用一份
+async fn cache_path() -> Result<PathBuf> {
+    Ok(git_backend::repo_context()
+        .await?
+        .common_dir
+        .join("matecode")
+        .join(CACHE_FILE))
+}
+
+fn by_frequency(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut items: Vec<(String, usize)> = counts.into_iter().collect();
+    items.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    items
+}
+
+fn starts_with_gitmoji(subject: &str) -> bool {
+    GITMOJI_CODE.is_match(subject)
+        || subject.chars().next().is_some_and(|c| {
+            matches!(c as u32, 0x1F300..=0x1FAFF | 0x2600..=0x27BF | 0x2B50 | 0x2B06 | 0x2194..=0x21AA)
+        })
+}
+
+/// 按中文字符和英文字母的比例判断，太短或只有符号时返回 `None`
+fn detect_language(text: &str) -> Option<SubjectLanguage> {
+    let cjk = text
+        .chars()
+        .filter(|c| matches!(*c as u32, 0x4E00..=0x9FFF | 0x3400..=0x4DBF))
+        .count();
+    let latin = text.chars().filter(|c| c.is_ascii_alphabetic()).count();
+    if cjk == 0 && latin == 0 {
+        None
+    } else if cjk * 3 >= latin {
+        // 一个汉字大约相当于一个英文单词，中文标题里夹杂函数名时仍然算中文
+        Some(SubjectLanguage::Chinese)
+    } else {
+        Some(SubjectLanguage::English)
+    }
+}
@@ -0,0 +1,368 @@
+//! src/symbols.rs
+//!
+//! 轻量的符号提取：用正则识别 Rust、Python、TypeScript/JavaScript、Go 和 Java 中的
+//! 函数、类型等声明，把每个 hunk 对应到它所在的条目，并列出新增、删除和签名变化的声明。
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// 每个文件每一类最多列出的条目数
+const MAX_ITEMS_PER_KIND: usize = 12;
+/// 签名最长保留的字符数
+const MAX_SIGNATURE_CHARS: usize = 120;
+
+struct Pattern {
+    kind: &'static str,
+    regex: Regex,
+}
+
+fn patterns(specs: &[(&'static str, &str)]) -> Vec<Pattern> {
+    specs
+        .iter()
+        .map(|(kind, pattern)| Pattern {
+            kind,
+            regex: Regex::new(pattern).expect("invalid symbol pattern"),
+        })
+        .collect()
+}
+
+// 每个正则的第一个捕获组是符号名称
+lazy_static! {
+    static ref RUST: Vec<Pattern> = patterns(&[
+        (
+            "fn",
+            r#"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:(?:const|async|unsafe|extern(?:\s+"[^"]*")?)\s+)*fn\s+([A-Za-z_]\w*)"#
+        ),
+        (
+            "struct",
+            r"^\s*(?:pub(?:\([^)]*\))?\s+)?struct\s+([A-Za-z_]\w*)"
+        ),
+        (
+            "enum",
+            r"^\s*(?:pub(?:\([^)]*\))?\s+)?enum\s+([A-Za-z_]\w*)"
+        ),
+        (
+            "trait",
+            r"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:unsafe\s+)?trait\s+([A-Za-z_]\w*)"
+        ),
+        (
+            "type",
+            r"^\s*(?:pub(?:\([^)]*\))?\s+)?type\s+([A-Za-z_]\w*)"
+        ),
+        (
+            "mod",
+            r"^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+([A-Za-z_]\w*)\s*\{"
+        ),
+        ("macro", r"^\s*macro_rules!\s*([A-Za-z_]\w*)"),
+        (
+            "impl",
+            r"^\s*(?:unsafe\s+)?impl(?:<[^>]*>)?\s+([^{]+?)\s*(?:\{|where\b|$)"
+        ),
+    ]);
+    static ref PYTHON: Vec<Pattern> = patterns(&[
+        ("def", r"^\s*(?:async\s+)?def\s+([A-Za-z_]\w*)"),
+        ("class", r"^\s*class\s+([A-Za-z_]\w*)"),
+    ]);
+    static ref TYPESCRIPT: Vec<Pattern> = patterns(&[
+        (
+            "function",
+            r"^\s*(?:export\s+)?(?:default\s+)?(?:async\s+)?function\s*\*?\s*([A-Za-z_$][\w$]*)"
+        ),
+        (
+            "class",
+            r"^\s*(?:export\s+)?(?:default\s+)?(?:abstract\s+)?class\s+([A-Za-z_$][\w$]*)"
+        ),
+        (
+            "interface",
+            r"^\s*(?:export\s+)?interface\s+([A-Za-z_$][\w$]*)"
+        ),
+        (
+            "type",
+            r"^\s*(?:export\s+)?type\s+([A-Za-z_$][\w$]*)(?:<[^>]*>)?\s*="
+        ),
+        (
+            "enum",
+            r"^\s*(?:export\s+)?(?:const\s+)?enum\s+([A-Za-z_$][\w$]*)"
+        ),
+        (
+            "function",
+            r"^\s*(?:export\s+)?(?:const|let|var)\s+([A-Za-z_$][\w$]*)\s*(?::[^=]+)?=\s*(?:async\s+)?(?:function\b|\([^)]*\)\s*(?::[^=]+)?=>|[A-Za-z_$][\w$]*\s*=>)"
+        ),
+        (
+            "method",
+            r"^\s+(?:(?:public|private|protected|static|async|readonly|override|get|set)\s+)*([A-Za-z_$][\w$]*)\s*(?:<[^>]*>)?\([^)]*\)\s*(?::\s*[^{]+)?\{\s*$"
+        ),
+    ]);
+    static ref GO: Vec<Pattern> = patterns(&[
+        ("func", r"^func\s+((?:\([^)]*\)\s*)?[A-Za-z_]\w*)"),
+        ("type", r"^type\s+([A-Za-z_]\w*)"),
+    ]);
+    static ref JAVA: Vec<Pattern> = patterns(&[
+        (
+            "class",
+            r"^\s*(?:(?:public|private|protected|static|final|abstract|sealed)\s+)*class\s+([A-Za-z_]\w*)"
+        ),
+        (
+            "interface",
+            r"^\s*(?:(?:public|private|protected|static|sealed)\s+)*@?interface\s+([A-Za-z_]\w*)"
+        ),
+        (
+            "enum",
+            r"^\s*(?:(?:public|private|protected|static)\s+)*enum\s+([A-Za-z_]\w*)"
+        ),
+        (
+            "record",
+            r"^\s*(?:(?:public|private|protected|static|final)\s+)*record\s+([A-Za-z_]\w*)"
+        ),
+        (
+            "method",
+            r"^\s*(?:(?:public|private|protected|static|final|abstract|synchronized|native|default)\s+)+(?:<[^>]*>\s*)?[\w<>\[\],.? ]+\s+([A-Za-z_]\w*)\s*\("
+        ),
+    ]);
+}
+
+/// 不会是方法名的关键字，避免 `if (x) {` 被当成方法
+const KEYWORDS: &[&str] = &[
+    "if", "for", "while", "switch", "catch", "return", "function", "else", "new", "do", "try",
+];
+
+fn language_patterns(path: &str) -> Option<&'static [Pattern]> {
+    let extension = Path::new(path).extension()?.to_str()?.to_lowercase();
+    let patterns: &'static Vec<Pattern> = match extension.as_str() {
+        "rs" => &RUST,
+        "py" | "pyi" => &PYTHON,
+        "ts" | "tsx" | "js" | "jsx" | "mjs" | "cjs" | "mts" | "cts" => &TYPESCRIPT,
+        "go" => &GO,
+        "java" => &JAVA,
+        _ => return None,
+    };
+    Some(patterns)
+}
+
+/// 一行声明
+#[derive(Debug, Clone)]
+struct Declaration {
+    /// 例如 `fn analyze_diff`、`impl LLMClient for OpenAIClient`
+    label: String,
+    signature: String,
+    indent: usize,
+}
+
+fn parse_declaration(patterns: &[Pattern], line: &str) -> Option<Declaration> {
+    patterns.iter().find_map(|pattern| {
+        let name = pattern.regex.captures(line)?.get(1)?.as_str();
+        let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+        if KEYWORDS.contains(&name.as_str()) {
+            return None;
+        }
+        let signature = line
+            .trim()
+            .trim_end_matches('{')
+            .trim_end_matches(':')
+            .trim_end();
+        Some(Declaration {
+            label: format!("{} {}", pattern.kind, name),
+            signature: signature.chars().take(MAX_SIGNATURE_CHARS).collect(),
+            indent: line.len() - line.trim_start().len(),
+        })
+    })
+}
+
+/// 单个文件的符号级变化
+#[derive(Debug, Clone, Default)]
+pub struct FileSymbols {
+    pub path: String,
+    /// 内部有改动的条目
+    pub modified: Vec<String>,
+    /// 新增的声明 (签名)
+    pub added: Vec<String>,
+    /// 删除的声明 (签名)
+    pub removed: Vec<String>,
+    /// 签名发生变化的声明 (旧, 新)
+    pub changed: Vec<(String, String)>,
+}
+
+impl FileSymbols {
+    fn is_empty(&self) -> bool {
+        self.modified.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+
+    fn render(&self) -> String {
+        let mut lines = vec![self.path.clone()];
+        let mut push = |label: &str, items: Vec<String>| {
+            if items.is_empty() {
+                return;
+            }
+            let hidden = items.len().saturating_sub(MAX_ITEMS_PER_KIND);
+            let mut shown: Vec<String> = items.into_iter().take(MAX_ITEMS_PER_KIND).collect();
+            if hidden > 0 {
+                shown.push(format!("... {hidden} more"));
+            }
+            lines.push(format!("  {label}: {}", shown.join("; ")));
+        };
+        push("modified", self.modified.clone());
+        push("added", self.added.clone());
+        push("removed", self.removed.clone());
+        push(
+            "signature changed",
+            self.changed
+                .iter()
+                .map(|(old, new)| format!("{old} => {new}"))
+                .collect(),
+        );
+        lines.join("\n")
+    }
+}
+
+/// 分析一个文件的所有 hunk；不支持的语言返回 `None`
+pub fn extract_file_symbols(path: &str, hunks: &[String]) -> Option<FileSymbols> {
+    let patterns = language_patterns(path)?;
+
+    let mut modified: Vec<String> = Vec::new();
+    let mut added: Vec<Declaration> = Vec::new();
+    let mut removed: Vec<Declaration> = Vec::new();
+
+    for hunk in hunks {
+        let mut lines = hunk.lines();
+        let header = lines.next().unwrap_or_default();
+
+        // hunk 头中 git 给出的所在函数 (顶层的那一行)
+        let mut stack: Vec<Declaration> = header
+            .splitn(3, "@@")
+            .nth(2)
+            .and_then(|context| {
+                parse_declaration(patterns, context.strip_prefix(' ').unwrap_or(context))
+            })
+            .into_iter()
+            .collect();
+
+        for line in lines {
+            let (marker, code) = line.split_at(line.len().min(1));
+            if code.trim().is_empty() {
+                continue;
+            }
+
+            if let Some(declaration) = parse_declaration(patterns, code) {
+                // 缩进不比它深的旧条目已经结束
+                while stack
+                    .last()
+                    .is_some_and(|open| open.indent >= declaration.indent)
+                {
+                    stack.pop();
+                }
+                match marker {
+                    "+" => added.push(declaration.clone()),
+                    "-" => removed.push(declaration.clone()),
+                    _ => {}
+                }
+                stack.push(declaration);
+                continue;
+            }
+
+            // 缩进回到条目所在层级：闭合括号仍属于该条目，其他代码已经在条目之外
+            let indent = code.len() - code.trim_start().len();
+            let closing = code.trim_start().starts_with(['}', ')', ']']);
+            if !closing {
+                while stack.last().is_some_and(|open| open.indent >= indent) {
+                    stack.pop();
+                }
+            }
+
+            if (marker == "+" || marker == "-") && !stack.is_empty() {
+                let label = stack
+                    .iter()
+                    .map(|declaration| declaration.label.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" > ");
+                if !modified.contains(&label) {
+                    modified.push(label);
+                }
+            }
+
+            if closing {
+                while stack.last().is_some_and(|open| open.indent >= indent) {
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    // 同名声明同时出现在新增和删除中：签名变化，或者只是挪了位置
+    let mut changed = Vec::new();
+    removed.retain(|old| {
+        let Some(index) = added.iter().position(|new| new.label == old.label) else {
+            return true;
+        };
+        let new = added.remove(index);
+        if new.signature != old.signature {
+            changed.push((old.signature.clone(), new.signature));
+        }
+        false
+    });
+
+    // 整个声明是新增或删除的，就不用再列为"内部有改动"
+    let declared: BTreeSet<&str> = added
+        .iter()
+        .chain(removed.iter())
+        .map(|declaration| declaration.label.as_str())
+        .collect();
+    modified.retain(|label| {
+        let innermost = label.rsplit(" > ").next().unwrap_or(label);
+        !declared.contains(innermost)
+    });
+
+    let symbols = FileSymbols {
+        path: path.to_string(),
+        modified,
+        added: added.into_iter().map(|d| d.signature).collect(),
+        removed: removed.into_iter().map(|d| d.signature).collect(),
+        changed,
+    };
+    (!symbols.is_empty()).then_some(symbols)
+}
+
+/// hunk 头中 git 给出的所在条目，例如 `fn analyze_diff`；
+/// 不支持的语言或无法识别时返回 hunk 头中的原始文本
+pub fn hunk_scope(path: &str, hunk: &str) -> Option<String> {
+    let header = hunk.lines().next()?;
+    let context = header.splitn(3, "@@").nth(2)?.trim();
+    if context.is_empty() {
+        return None;
+    }
+    let declaration = language_patterns(path)
+        .and_then(|patterns| parse_declaration(patterns, context))
+        .map(|declaration| declaration.label);
+    Some(declaration.unwrap_or_else(|| context.chars().take(MAX_SIGNATURE_CHARS).collect()))
+}
+
+/// hunk 中被删除的声明，例如 `fn old_helper`；不支持的语言返回空
+pub fn removed_declarations(path: &str, hunk: &str) -> Vec<String> {
+    let Some(patterns) = language_patterns(path) else {
+        return Vec::new();
+    };
+    hunk.lines()
+        .skip(1)
+        .filter_map(|line| line.strip_prefix('-'))
+        .filter_map(|code| parse_declaration(patterns, code))
+        .map(|declaration| declaration.label)
+        .collect()
+}
+
+/// 渲染 `files` 中各文件的符号变化，用于 `{symbol_changes}`
+pub fn render_symbol_changes(symbols: &[FileSymbols], files: &[String]) -> String {
+    let rendered: Vec<String> = symbols
+        .iter()
+        .filter(|symbols| files.contains(&symbols.path))
+        .map(FileSymbols::render)
+        .collect();
+    if rendered.is_empty() {
+        "(no symbol-level changes detected)".to_string()
+    } else {
+        rendered.join("\n")
+    }
+}